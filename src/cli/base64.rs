@@ -1,9 +1,20 @@
 use super::verify_file;
-use crate::CmdExecutor;
+use crate::{
+    get_reader, get_writer, process_decode, process_decode_auto, process_decode_data_uri,
+    process_decode_doc, process_encode, process_encode_data_uri, process_encode_doc, CmdExecutor,
+    DocPath,
+};
 use anyhow::Ok;
 use clap::Parser;
 use std::{fmt, str::FromStr};
 
+const FORMAT_HELP: &str = "standard, standard-nopad, urlsafe (no padding), urlsafe-pad, hex, hex-upper, base32, crockford, base58, ascii85 or z85";
+
+const PATH_HELP: &str = "only transform the string values at this JSONPath ($.data.*) or dotted path (data.password) inside a JSON/YAML document, can be repeated";
+
+const DECODE_FORMAT_HELP: &str =
+    "any encode format, or auto to detect base64, hex or base32 from the first 8 KiB of input";
+
 #[derive(Debug, Parser)]
 pub enum Base64SubCommand {
     #[command(name = "encode")]
@@ -17,34 +28,88 @@ pub enum Base64SubCommand {
 pub struct Base64EncodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
-    #[arg(long, value_parser = parse_base64_format, default_value = "standard")]
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(long, value_parser = parse_base64_format, default_value = "standard", help = FORMAT_HELP)]
     pub format: Base64Format,
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "wrap lines at this many columns (64 for PEM, 76 for MIME), 0 to disable"
+    )]
+    pub wrap: usize,
+    #[arg(long, conflicts_with_all = ["format", "wrap"], help = "emit data:<mime>;base64,... with the mime type sniffed from the input")]
+    pub data_uri: bool,
+    #[arg(long, conflicts_with_all = ["wrap", "data_uri"], help = PATH_HELP)]
+    pub path: Vec<DocPath>,
 }
 
 #[derive(Debug, Parser)]
 pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
-    #[arg(long, value_parser = parse_base64_format, default_value = "standard")]
-    pub format: Base64Format,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(long, value_parser = parse_base64_decode_format, default_value = "standard", help = DECODE_FORMAT_HELP)]
+    pub format: Base64DecodeFormat,
+    #[arg(
+        long,
+        conflicts_with = "format",
+        help = "input is a data: uri, its header is stripped"
+    )]
+    pub data_uri: bool,
+    #[arg(long, conflicts_with = "data_uri", help = PATH_HELP)]
+    pub path: Vec<DocPath>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Base64Format {
     Standard,
+    StandardNoPad,
     UrlSafe,
+    UrlSafePad,
+    Hex,
+    HexUpper,
+    Base32,
+    Crockford,
+    Base58,
+    Ascii85,
+    Z85,
+}
+
+/// Decoding can also guess the format from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base64DecodeFormat {
+    Auto,
+    Format(Base64Format),
 }
 
 fn parse_base64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
     format.parse()
 }
 
+fn parse_base64_decode_format(format: &str) -> Result<Base64DecodeFormat, anyhow::Error> {
+    match format {
+        "auto" => Ok(Base64DecodeFormat::Auto),
+        _ => Ok(Base64DecodeFormat::Format(format.parse()?)),
+    }
+}
+
 impl FromStr for Base64Format {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "standard" => Ok(Base64Format::Standard),
+            "standard-nopad" => Ok(Base64Format::StandardNoPad),
             "urlsafe" => Ok(Base64Format::UrlSafe),
+            "urlsafe-pad" => Ok(Base64Format::UrlSafePad),
+            "hex" => Ok(Base64Format::Hex),
+            "hex-upper" => Ok(Base64Format::HexUpper),
+            "base32" => Ok(Base64Format::Base32),
+            "crockford" => Ok(Base64Format::Crockford),
+            "base58" => Ok(Base64Format::Base58),
+            "ascii85" => Ok(Base64Format::Ascii85),
+            "z85" => Ok(Base64Format::Z85),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -59,20 +124,55 @@ impl From<Base64Format> for &'static str {
     fn from(format: Base64Format) -> Self {
         match format {
             Base64Format::Standard => "standard",
+            Base64Format::StandardNoPad => "standard-nopad",
             Base64Format::UrlSafe => "urlsafe",
+            Base64Format::UrlSafePad => "urlsafe-pad",
+            Base64Format::Hex => "hex",
+            Base64Format::HexUpper => "hex-upper",
+            Base64Format::Base32 => "base32",
+            Base64Format::Crockford => "crockford",
+            Base64Format::Base58 => "base58",
+            Base64Format::Ascii85 => "ascii85",
+            Base64Format::Z85 => "z85",
         }
     }
 }
 
 impl CmdExecutor for Base64EncodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        todo!()
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        if self.data_uri {
+            process_encode_data_uri(&mut reader, &mut writer)?;
+            return Ok(());
+        }
+        if !self.path.is_empty() {
+            return process_encode_doc(&mut reader, &mut writer, &self.path, self.format);
+        }
+        process_encode(&mut reader, &mut writer, self.format, self.wrap)
     }
 }
 
 impl CmdExecutor for Base64DecodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        todo!()
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        if self.data_uri {
+            let mime = process_decode_data_uri(&mut reader, &mut writer)?;
+            eprintln!("mime type: {}", mime);
+            return Ok(());
+        }
+        if !self.path.is_empty() {
+            return process_decode_doc(&mut reader, &mut writer, &self.path, self.format);
+        }
+        match self.format {
+            Base64DecodeFormat::Format(format) => process_decode(&mut reader, &mut writer, format),
+            Base64DecodeFormat::Auto => {
+                let format = process_decode_auto(&mut reader, &mut writer)?;
+                eprintln!("detected format: {}", format);
+                Ok(())
+            }
+        }
     }
 }
 
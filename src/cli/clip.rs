@@ -0,0 +1,68 @@
+use clap::Args;
+use std::{fmt, str::FromStr, time::Duration};
+
+#[derive(Debug, Args)]
+pub struct ClipOpts {
+    #[arg(long, help = "copy to the clipboard instead of printing")]
+    pub clip: bool,
+    #[arg(long, default_value = "auto", value_parser = parse_clip_backend)]
+    pub clip_backend: ClipBackend,
+    #[arg(
+        long,
+        default_value_t = 30,
+        help = "seconds before the clipboard is cleared, 0 to keep"
+    )]
+    pub clip_timeout: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ClipBackend {
+    Auto,
+    Osc52,
+    Xclip,
+    WlCopy,
+    Pbcopy,
+}
+
+impl ClipOpts {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.clip_timeout)
+    }
+}
+
+fn parse_clip_backend(backend: &str) -> Result<ClipBackend, anyhow::Error> {
+    backend.parse()
+}
+
+impl FromStr for ClipBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ClipBackend::Auto),
+            "osc52" => Ok(ClipBackend::Osc52),
+            "xclip" => Ok(ClipBackend::Xclip),
+            "wl-copy" => Ok(ClipBackend::WlCopy),
+            "pbcopy" => Ok(ClipBackend::Pbcopy),
+            _ => Err(anyhow::anyhow!("Invalid clipboard backend")),
+        }
+    }
+}
+
+impl From<ClipBackend> for &'static str {
+    fn from(backend: ClipBackend) -> Self {
+        match backend {
+            ClipBackend::Auto => "auto",
+            ClipBackend::Osc52 => "osc52",
+            ClipBackend::Xclip => "xclip",
+            ClipBackend::WlCopy => "wl-copy",
+            ClipBackend::Pbcopy => "pbcopy",
+        }
+    }
+}
+
+impl fmt::Display for ClipBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -1,32 +1,234 @@
-use crate::{process_genpwd, CmdExecutor};
-use clap::Parser;
+use super::ClipOpts;
+use crate::{
+    clipboard_backend, process_clip, process_genpwd, process_genpwd_derive, process_genpwd_pattern,
+    process_genpwd_pin, process_genpwd_pronounceable, process_pwd_strength, read_secret,
+    CmdExecutor, DeriveParams, MIN_PIN_LENGTH,
+};
+use clap::{Args, Parser};
+use std::{fmt, str::FromStr};
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPWDOpts {
+    #[command(subcommand)]
+    pub cmd: Option<GenPWDSubCommand>,
+    #[command(flatten)]
+    pub classes: PwdClassOpts,
+    // --pronounceable only honours --length and --no-upper-case, --pin and --pattern
+    // set their own length and classes
+    #[arg(
+        long,
+        conflicts_with_all = ["pin", "pattern", "lower_case", "no_lower_case", "numbers", "no_numbers", "symbols", "no_symbols"],
+        help = "alternate consonants and vowels"
+    )]
+    pub pronounceable: bool,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(MIN_PIN_LENGTH as i64..),
+        conflicts_with_all = ["pattern", "length", "upper_case", "no_upper_case", "lower_case", "no_lower_case", "numbers", "no_numbers", "symbols", "no_symbols"],
+        help = "numeric code with this many digits"
+    )]
+    pub pin: Option<u8>,
+    #[arg(
+        long,
+        conflicts_with_all = ["length", "upper_case", "no_upper_case", "lower_case", "no_lower_case", "numbers", "no_numbers", "symbols", "no_symbols"],
+        help = "template like Cvcc-9999: C/c consonant, V/v vowel, A/a letter, 9 digit, s symbol, x any, \\ escapes"
+    )]
+    pub pattern: Option<String>,
+    #[command(flatten)]
+    pub clip: ClipOpts,
+}
+
+#[derive(Debug, Args)]
+pub struct PwdClassOpts {
     #[arg(short, long, default_value_t = 16)]
     pub length: u8,
-    #[arg(long)]
+    // every class is on by default, the last of `--x`/`--no-x` wins
+    #[arg(long, overrides_with = "no_upper_case")]
+    pub upper_case: bool,
+    #[arg(long, overrides_with = "upper_case")]
     pub no_upper_case: bool,
-    #[arg(long, default_value_t = true)]
+    #[arg(long, overrides_with = "no_lower_case")]
     pub lower_case: bool,
-    #[arg(long, default_value_t = true)]
+    #[arg(long, overrides_with = "lower_case")]
+    pub no_lower_case: bool,
+    #[arg(long, overrides_with = "no_numbers")]
     pub numbers: bool,
-    #[arg(long, default_value_t = true)]
+    #[arg(long, overrides_with = "numbers")]
+    pub no_numbers: bool,
+    #[arg(long, overrides_with = "no_symbols")]
     pub symbols: bool,
+    #[arg(long, overrides_with = "symbols")]
+    pub no_symbols: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum GenPWDSubCommand {
+    #[command(
+        about = "estimate the strength of a password read from stdin",
+        long_about = "estimate the strength of a password read from stdin\n\n\
+            The built-in dictionaries are small (a few hundred common passwords and \
+            English words), so words outside them are scored as bruteforce and the \
+            estimate can be too optimistic for passphrases built from real words."
+    )]
+    Strength(PwdStrengthOpts),
+    #[command(about = "derive a site password from a master password")]
+    Derive(PwdDeriveOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdStrengthOpts {
+    #[arg(long, help = "print the estimation as json")]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdDeriveOpts {
+    #[arg(long)]
+    pub site: String,
+    #[arg(long)]
+    pub login: String,
+    #[arg(long, default_value_t = 1)]
+    pub counter: u32,
+    #[arg(long, default_value = "argon2id", value_parser = parse_pwd_kdf)]
+    pub kdf: PwdKdf,
+    #[command(flatten)]
+    pub classes: PwdClassOpts,
+    #[command(flatten)]
+    pub clip: ClipOpts,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PwdKdf {
+    Argon2id,
+    Scrypt,
+}
+
+fn parse_pwd_kdf(kdf: &str) -> Result<PwdKdf, anyhow::Error> {
+    kdf.parse()
+}
+
+impl FromStr for PwdKdf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "argon2id" => Ok(PwdKdf::Argon2id),
+            "scrypt" => Ok(PwdKdf::Scrypt),
+            _ => Err(anyhow::anyhow!("Invalid kdf")),
+        }
+    }
+}
+
+impl From<PwdKdf> for &'static str {
+    fn from(kdf: PwdKdf) -> Self {
+        match kdf {
+            PwdKdf::Argon2id => "argon2id",
+            PwdKdf::Scrypt => "scrypt",
+        }
+    }
+}
+
+impl fmt::Display for PwdKdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
 }
 
 impl CmdExecutor for GenPWDOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let ret = process_genpwd(
-            self.length,
-            self.no_upper_case,
-            self.lower_case,
-            self.numbers,
-            self.symbols,
-        )?;
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
 
-        println!("{}", ret);
+        let ret = if self.pronounceable {
+            process_genpwd_pronounceable(self.classes.length, self.classes.no_upper_case)?
+        } else if let Some(digits) = self.pin {
+            process_genpwd_pin(digits)?
+        } else if let Some(pattern) = &self.pattern {
+            process_genpwd_pattern(pattern)?
+        } else {
+            process_genpwd(
+                self.classes.length,
+                self.classes.no_upper_case,
+                !self.classes.no_lower_case,
+                !self.classes.no_numbers,
+                !self.classes.no_symbols,
+            )?
+        };
+
+        output_password(&ret, &self.clip).await
+    }
+}
+
+impl CmdExecutor for GenPWDSubCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            GenPWDSubCommand::Strength(opts) => opts.execute().await,
+            GenPWDSubCommand::Derive(opts) => opts.execute().await,
+        }
+    }
+}
+
+impl CmdExecutor for PwdStrengthOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let password = read_secret("Password: ")?;
+        let ret = process_pwd_strength(&password);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&ret)?);
+            return Ok(());
+        }
+
+        println!("score: {}/4", ret.score);
+        println!("guesses: 10^{:.1}", ret.guesses_log10);
+        println!("crack times:");
+        for t in &ret.crack_times {
+            println!("  {}: {}", t.scenario, t.display);
+        }
+        if let Some(warning) = &ret.warning {
+            println!("warning: {}", warning);
+        }
+        if !ret.suggestions.is_empty() {
+            println!("suggestions:");
+            for s in &ret.suggestions {
+                println!("  - {}", s);
+            }
+        }
 
         Ok(())
     }
 }
+
+impl CmdExecutor for PwdDeriveOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let master = read_secret("Master password: ")?;
+        let params = DeriveParams {
+            site: &self.site,
+            login: &self.login,
+            counter: self.counter,
+            kdf: self.kdf,
+        };
+        let ret = process_genpwd_derive(
+            &master,
+            &params,
+            self.classes.length,
+            self.classes.no_upper_case,
+            !self.classes.no_lower_case,
+            !self.classes.no_numbers,
+            !self.classes.no_symbols,
+        )?;
+
+        output_password(&ret, &self.clip).await
+    }
+}
+
+async fn output_password(password: &str, clip: &ClipOpts) -> anyhow::Result<()> {
+    if clip.clip {
+        let mut backend = clipboard_backend(clip.clip_backend);
+        return process_clip(backend.as_mut(), password.as_bytes(), clip.timeout()).await;
+    }
+
+    println!("{}", password);
+    Ok(())
+}
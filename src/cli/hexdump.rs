@@ -0,0 +1,44 @@
+use super::verify_file;
+use crate::{
+    get_reader, get_writer, process_hexdump, process_hexdump_reverse, CmdExecutor, HexdumpLayout,
+};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct HexdumpOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(short, long, default_value_t = 16, value_parser = clap::value_parser!(u16).range(1..=256), help = "bytes per line")]
+    pub cols: u16,
+    #[arg(
+        short,
+        long,
+        default_value_t = 2,
+        help = "bytes per group, 0 for no spaces"
+    )]
+    pub group: usize,
+    #[arg(long, default_value_t = 0, help = "skip this many bytes first")]
+    pub offset: u64,
+    #[arg(short, long, help = "stop after this many bytes")]
+    pub length: Option<u64>,
+    #[arg(short, long, conflicts_with_all = ["cols", "group", "offset", "length"], help = "convert a dump back to binary")]
+    pub reverse: bool,
+}
+
+impl CmdExecutor for HexdumpOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        if self.reverse {
+            return process_hexdump_reverse(&mut reader, &mut writer);
+        }
+
+        let layout = HexdumpLayout {
+            cols: self.cols as usize,
+            group: self.group,
+        };
+        process_hexdump(&mut reader, &mut writer, layout, self.offset, self.length)
+    }
+}
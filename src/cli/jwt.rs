@@ -0,0 +1,200 @@
+use super::{read_key, verify_file};
+use crate::{
+    get_content, jwt_times, process_jwt_decode, process_jwt_sign, process_jwt_verify,
+    process_jwt_verify_signature, CmdExecutor, JwtValidation,
+};
+use anyhow::Context;
+use clap::Parser;
+use serde_json::{Map, Value};
+use std::{fmt, str::FromStr, time::Duration};
+
+#[derive(Debug, Parser)]
+pub enum JwtSubCommand {
+    #[command(about = "decode a JWT, the signature is only checked when a key is given")]
+    Decode(JwtDecodeOpts),
+    #[command(about = "sign claims into a JWT")]
+    Sign(JwtSignOpts),
+    #[command(about = "verify a JWT's signature, exp, nbf, aud and iss")]
+    Verify(JwtVerifyOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtDecodeOpts {
+    #[arg(default_value = "-", help = "the token, - to read it from stdin")]
+    pub token: String,
+    #[arg(
+        short,
+        long,
+        value_parser = verify_file,
+        requires = "alg",
+        help = "HS256 secret (raw or hmac:<base64url>), or EdDSA public key (SPKI PEM, OpenSSH, JWK or 32 raw bytes) to verify the signature with"
+    )]
+    pub key: Option<String>,
+    #[arg(
+        long,
+        value_parser = parse_jwt_alg,
+        requires = "key",
+        help = "the algorithm the token must be signed with, the header is not trusted"
+    )]
+    pub alg: Option<JwtAlg>,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtSignOpts {
+    #[arg(short, long, value_parser = verify_file, help = "HS256 secret (raw or hmac:<base64url>), or EdDSA private key (PKCS#8 PEM, OpenSSH, JWK, or 32 or 64 raw bytes)")]
+    pub key: String,
+    #[arg(long, default_value = "HS256", value_parser = parse_jwt_alg)]
+    pub alg: JwtAlg,
+    #[arg(long, value_parser = verify_file, help = "JSON object with the claims")]
+    pub claims: Option<String>,
+    #[arg(
+        long = "claim",
+        value_parser = parse_claim,
+        help = "k=v, the value is parsed as JSON if it can be, can be repeated"
+    )]
+    pub claim: Vec<(String, Value)>,
+    #[arg(long, value_parser = humantime::parse_duration, help = "expire after this long, e.g. 1h or 30m")]
+    pub exp: Option<Duration>,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtVerifyOpts {
+    #[arg(default_value = "-", help = "the token, - to read it from stdin")]
+    pub token: String,
+    #[arg(short, long, value_parser = verify_file, help = "HS256 secret (raw or hmac:<base64url>), or EdDSA public key (SPKI PEM, OpenSSH, JWK or 32 raw bytes)")]
+    pub key: String,
+    #[arg(long, default_value = "HS256", value_parser = parse_jwt_alg)]
+    pub alg: JwtAlg,
+    #[arg(long, help = "required audience")]
+    pub aud: Option<String>,
+    #[arg(long, help = "required issuer")]
+    pub iss: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlg {
+    Hs256,
+    EdDsa,
+}
+
+fn parse_jwt_alg(alg: &str) -> Result<JwtAlg, anyhow::Error> {
+    alg.parse()
+}
+
+fn parse_claim(claim: &str) -> Result<(String, Value), anyhow::Error> {
+    let (k, v) = claim
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("claims are given as k=v"))?;
+    // numbers, booleans and arrays keep their type, anything else is a string
+    let v = serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.to_string()));
+    Ok((k.to_string(), v))
+}
+
+impl FromStr for JwtAlg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HS256" | "hs256" => Ok(JwtAlg::Hs256),
+            "EdDSA" | "eddsa" => Ok(JwtAlg::EdDsa),
+            _ => Err(anyhow::anyhow!("Invalid algorithm")),
+        }
+    }
+}
+
+impl From<JwtAlg> for &'static str {
+    fn from(alg: JwtAlg) -> Self {
+        match alg {
+            JwtAlg::Hs256 => "HS256",
+            JwtAlg::EdDsa => "EdDSA",
+        }
+    }
+}
+
+impl fmt::Display for JwtAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExecutor for JwtDecodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let jwt = process_jwt_decode(&read_token(&self.token)?)?;
+
+        println!("header:\n{}", serde_json::to_string_pretty(&jwt.header)?);
+        println!("claims:\n{}", serde_json::to_string_pretty(&jwt.claims)?);
+        let times = jwt_times(&jwt.claims, chrono::Utc::now().timestamp());
+        if !times.is_empty() {
+            println!("times:");
+            for t in &times {
+                println!("  {}: {} ({})", t.claim, t.date, t.note);
+            }
+        }
+
+        match (self.key, self.alg) {
+            (Some(key), Some(alg)) => {
+                let key = read_key(&key, self.token == "-")?;
+                process_jwt_verify_signature(&jwt, alg, &key).context("signature: invalid")?;
+                println!("signature: verified");
+            }
+            _ => println!("signature: not verified, no key given"),
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for JwtSignOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let key = read_key(&self.key, self.claims.as_deref() == Some("-"))?;
+        let mut claims = match &self.claims {
+            Some(path) => match serde_json::from_slice(&get_content(path)?)? {
+                Value::Object(claims) => claims,
+                _ => anyhow::bail!("claims file must hold a JSON object"),
+            },
+            None => Map::new(),
+        };
+        claims.extend(self.claim);
+        if let Some(exp) = self.exp {
+            let now = chrono::Utc::now().timestamp();
+            claims.insert("iat".to_string(), now.into());
+            claims.insert("exp".to_string(), (now + exp.as_secs() as i64).into());
+        }
+
+        println!("{}", process_jwt_sign(&claims, &key, self.alg)?);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for JwtVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let token = read_token(&self.token)?;
+        let key = read_key(&self.key, self.token == "-")?;
+        let validation = JwtValidation {
+            aud: self.aud.as_deref(),
+            iss: self.iss.as_deref(),
+            now: chrono::Utc::now().timestamp(),
+        };
+        let jwt = process_jwt_verify(&token, &key, self.alg, &validation)?;
+
+        println!("token verified");
+        println!("{}", serde_json::to_string_pretty(&jwt.claims)?);
+        Ok(())
+    }
+}
+
+fn read_token(token: &str) -> anyhow::Result<String> {
+    match token {
+        "-" => Ok(String::from_utf8(get_content("-")?)?),
+        token => Ok(token.to_string()),
+    }
+}
+
+impl CmdExecutor for JwtSubCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            JwtSubCommand::Decode(opts) => opts.execute().await,
+            JwtSubCommand::Sign(opts) => opts.execute().await,
+            JwtSubCommand::Verify(opts) => opts.execute().await,
+        }
+    }
+}
@@ -1,19 +1,31 @@
 mod base64;
+mod clip;
 mod csv;
 mod genpwd;
+mod hexdump;
 mod http;
+mod jwt;
 mod text;
-use crate::CmdExecutor;
+mod url;
+use crate::{get_content, is_encrypted_key, process_key_decrypt, read_secret, CmdExecutor};
 
 pub use self::{
-    base64::{Base64Format, Base64SubCommand},
+    base64::{Base64DecodeFormat, Base64Format, Base64SubCommand},
+    clip::{ClipBackend, ClipOpts},
     csv::OutputFormat,
+    genpwd::PwdKdf,
     http::HttpSubCommand,
-    text::{TextSignFormat, TextSubCommand},
+    jwt::{JwtAlg, JwtSubCommand},
+    text::{KeyFormat, TextSignFormat, TextSubCommand},
+    url::{UrlComponent, UrlSubCommand},
 };
-use self::{csv::CsvOpts, genpwd::GenPWDOpts};
+use self::{csv::CsvOpts, genpwd::GenPWDOpts, hexdump::HexdumpOpts};
+use anyhow::Context;
 use clap::Parser;
-use std::path::{Path, PathBuf};
+use std::{
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Parser)]
 #[command(name = "zcli", version, author, about, long_about = None)]
@@ -31,9 +43,18 @@ pub enum SubCommand {
     #[command(subcommand)]
     Base64(Base64SubCommand),
     #[command(subcommand)]
+    Url(UrlSubCommand),
+    #[command(
+        name = "hexdump",
+        about = "dump binary data as hex like xxd, or reverse a dump"
+    )]
+    Hexdump(HexdumpOpts),
+    #[command(subcommand)]
     Text(TextSubCommand),
     #[command(subcommand)]
     Http(HttpSubCommand),
+    #[command(subcommand)]
+    Jwt(JwtSubCommand),
 }
 
 impl CmdExecutor for SubCommand {
@@ -45,6 +66,12 @@ impl CmdExecutor for SubCommand {
             SubCommand::Base64(opts) => {
                 opts.execute().await?;
             }
+            SubCommand::Url(opts) => {
+                opts.execute().await?;
+            }
+            SubCommand::Hexdump(opts) => {
+                opts.execute().await?;
+            }
             SubCommand::GenPWD(opts) => {
                 opts.execute().await?;
             }
@@ -54,6 +81,9 @@ impl CmdExecutor for SubCommand {
             SubCommand::Text(opts) => {
                 opts.execute().await?;
             }
+            SubCommand::Jwt(opts) => {
+                opts.execute().await?;
+            }
         }
 
         Ok(())
@@ -76,3 +106,30 @@ fn verify_path(path: &str) -> Result<PathBuf, &'static str> {
         Err("Path not exists")
     }
 }
+
+/// Read a key file, asking for its passphrase if it was generated with `--encrypt`.
+/// `stdin_is_data` when the input is read from stdin, so it can't carry the passphrase.
+fn read_key(path: &str, stdin_is_data: bool) -> anyhow::Result<Vec<u8>> {
+    let key = get_content(path)?;
+    if !is_encrypted_key(&key) {
+        return Ok(key);
+    }
+    let prompt = format!("Passphrase for {}: ", path);
+    let passphrase = read_password(&prompt, stdin_is_data || path == "-", false)?;
+    process_key_decrypt(&key, &passphrase).with_context(|| format!("can't decrypt {}", path))
+}
+
+/// Secrets never come from argv. They are asked on the terminal, or for scripts read
+/// from piped stdin when stdin doesn't carry the data itself.
+fn read_password(prompt: &str, stdin_is_data: bool, confirm: bool) -> anyhow::Result<String> {
+    if !stdin_is_data && !io::stdin().is_terminal() {
+        return read_secret(prompt);
+    }
+    let password = rpassword::prompt_password(prompt)?;
+    if confirm
+        && rpassword::prompt_password(format!("Confirm {}", prompt.to_lowercase()))? != password
+    {
+        anyhow::bail!("passwords do not match");
+    }
+    Ok(password)
+}
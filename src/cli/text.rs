@@ -1,12 +1,22 @@
-use super::{verify_file, verify_path};
+use super::{read_key, read_password, verify_file, verify_path, ClipOpts};
 use crate::{
-    get_content, get_reader, process_text_key_generate, process_text_sign, process_text_verify,
-    CmdExecutor,
+    clipboard_backend, get_content, get_reader, get_writer, process_clip, process_key_encrypt,
+    process_manifest_build, process_manifest_check, process_text_decrypt,
+    process_text_decrypt_password, process_text_encrypt, process_text_encrypt_password,
+    process_text_key_generate, process_text_sign, process_text_sign_envelope,
+    process_text_sign_sshsig, process_text_verify, process_text_verify_envelope,
+    process_text_verify_sshsig, secret_key_name, CmdExecutor, PasswordKdfParams, SignatureEnvelope,
+    MAX_M_COST, MAX_T_COST, SSHSIG_DEFAULT_NAMESPACE,
 };
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use clap::Parser;
-use std::{fmt, path::PathBuf, str::FromStr};
-use tokio::fs;
+use std::{
+    fmt,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::{fs, io::AsyncWriteExt};
 
 #[derive(Debug, Parser)]
 pub enum TextSubCommand {
@@ -14,44 +24,261 @@ pub enum TextSubCommand {
     Sign(TextSignOpts),
     #[command(about = "verify a signature")]
     Verify(TextVeriryOpts),
-    #[command(about = "generate a random blake3 or 15519 key pair")]
+    #[command(about = "generate a random key, or key pair, for a signature format")]
     Generate(KeyGenerateOpts),
+    #[command(about = "hash every file in a directory and sign the manifest")]
+    SignManifest(TextSignManifestOpts),
+    #[command(about = "check a directory against its signed manifest")]
+    VerifyManifest(TextVerifyManifestOpts),
+    #[command(about = "encrypt a text with XChaCha20-Poly1305")]
+    Encrypt(TextEncryptOpts),
+    #[command(about = "decrypt a text encrypted with text encrypt")]
+    Decrypt(TextDecryptOpts),
 }
 
+const FORMAT_HELP: &str =
+    "blake3, ed25519, ed25519ph, hmac-sha256, hmac-sha512, ecdsa-p256, ecdsa-secp256k1 or sshsig; \
+    ed25519 is plain Ed25519 and reads the input into memory, ed25519ph signs its SHA-512 \
+    so large inputs are streamed, the two don't verify each other's signatures";
+
 #[derive(Debug, Parser)]
 pub struct TextSignOpts {
-    #[arg(short, long, value_parser = verify_file, default_value = "-")]
-    pub input: String,
-    #[arg(short, long, value_parser = verify_file)]
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "can be repeated with --detached")]
+    pub input: Vec<String>,
+    #[arg(short, long, value_parser = verify_file, help = "for sshsig an OpenSSH private key")]
     pub key: String,
-    #[arg(long, default_value = "blake3", value_parser = parse_text_sign_format)]
+    #[arg(long, default_value = "blake3", value_parser = parse_text_sign_format, help = FORMAT_HELP)]
     pub format: TextSignFormat,
+    #[arg(long, value_parser = parse_envelope_format, default_value = "compact", help = "compact, json, or raw for a bare signature, sshsig signatures are always armored")]
+    pub envelope: EnvelopeFormat,
+    #[arg(long, default_value = SSHSIG_DEFAULT_NAMESPACE, help = "sshsig namespace, e.g. git or file")]
+    pub namespace: String,
+    #[arg(long, help = "free text stored, and signed, in the envelope")]
+    pub comment: Option<String>,
+    #[arg(long, help = "write the signature of each input to <input>.sig")]
+    pub detached: bool,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextVeriryOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
+    #[arg(
+        short,
+        long,
+        value_parser = verify_file,
+        help = "the key, or an allowed_signers file for SSH signatures"
+    )]
+    pub key: String,
+    #[arg(
+        long,
+        help = "a signature envelope, a bare or SSH signature or a file holding one, <input>.sig by default"
+    )]
+    pub sig: Option<String>,
+    #[arg(long, value_parser = parse_text_sign_format, help = "only needed for bare signatures, blake3 by default")]
+    pub format: Option<TextSignFormat>,
+    #[arg(long, default_value = SSHSIG_DEFAULT_NAMESPACE, help = "namespace SSH signatures must be made for")]
+    pub namespace: String,
+    #[arg(
+        long,
+        help = "principal the SSH signing key must be allowed for, any by default"
+    )]
+    pub identity: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct TextSignManifestOpts {
+    #[arg(value_parser = verify_path)]
+    pub dir: PathBuf,
     #[arg(short, long, value_parser = verify_file)]
     pub key: String,
-    #[arg(long)]
-    pub sig: String,
-    #[arg(long, default_value = "blake3", value_parser = parse_text_sign_format)]
+    #[arg(long, default_value = "ed25519", value_parser = parse_text_sign_format)]
     pub format: TextSignFormat,
+    #[arg(
+        short,
+        long,
+        help = "where to write the manifest, <dir>/MANIFEST.b3 by default, its signature goes next to it"
+    )]
+    pub manifest: Option<PathBuf>,
+    #[arg(long, help = "free text stored, and signed, in the envelope")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct TextVerifyManifestOpts {
+    #[arg(value_parser = verify_path)]
+    pub dir: PathBuf,
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+    #[arg(short, long, help = "<dir>/MANIFEST.b3 by default")]
+    pub manifest: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct TextEncryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(short, long, value_parser = verify_file, required_unless_present = "password", help = "32 byte key, e.g. a generated blake3.txt")]
+    pub key: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "key",
+        help = "derive the key from a password asked on the terminal"
+    )]
+    pub password: bool,
+    #[arg(
+        long,
+        default_value_t = PasswordKdfParams::default().m_cost,
+        value_parser = clap::value_parser!(u32).range(8..=MAX_M_COST as i64),
+        requires = "password",
+        help = "argon2id memory in KiB"
+    )]
+    pub kdf_memory: u32,
+    #[arg(
+        long,
+        default_value_t = PasswordKdfParams::default().t_cost,
+        value_parser = clap::value_parser!(u32).range(1..=MAX_T_COST as i64),
+        requires = "password",
+        help = "argon2id iterations"
+    )]
+    pub kdf_iterations: u32,
+    #[arg(long, help = "write raw bytes instead of URL-safe base64")]
+    pub raw: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct TextDecryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(short, long, value_parser = verify_file, required_unless_present = "password")]
+    pub key: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "key",
+        help = "the input was encrypted with --password"
+    )]
+    pub password: bool,
+    #[arg(long, help = "the input is raw bytes instead of URL-safe base64")]
+    pub raw: bool,
 }
 
 #[derive(Debug, Parser)]
 pub struct KeyGenerateOpts {
     #[arg(long, default_value = "blake3", value_parser = parse_text_sign_format)]
     pub format: TextSignFormat,
+    #[arg(long, default_value = "raw", value_parser = parse_key_format, help = "how key pairs are written: raw, pem, openssh or jwk")]
+    pub key_format: KeyFormat,
     #[arg(short, long, value_parser = verify_path)]
     pub output_path: PathBuf,
+    // the clipboard would get the armored ciphertext, and copying the plain key
+    // instead would defeat the encryption
+    #[arg(
+        long,
+        conflicts_with = "clip",
+        help = "protect the private key with a passphrase, asked on the terminal"
+    )]
+    pub encrypt: bool,
+    #[command(flatten)]
+    pub clip: ClipOpts,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum TextSignFormat {
     Blake3,
     Ed25519,
+    Ed25519ph,
+    HmacSha256,
+    HmacSha512,
+    EcdsaP256,
+    EcdsaSecp256k1,
+    SshSig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    Raw,
+    Pem,
+    OpenSsh,
+    Jwk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeFormat {
+    Compact,
+    Json,
+    Raw,
+}
+
+fn parse_envelope_format(format: &str) -> Result<EnvelopeFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for EnvelopeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compact" => Ok(EnvelopeFormat::Compact),
+            "json" => Ok(EnvelopeFormat::Json),
+            "raw" => Ok(EnvelopeFormat::Raw),
+            _ => Err(anyhow::anyhow!("Invalid envelope format")),
+        }
+    }
+}
+
+impl From<EnvelopeFormat> for &'static str {
+    fn from(format: EnvelopeFormat) -> Self {
+        match format {
+            EnvelopeFormat::Compact => "compact",
+            EnvelopeFormat::Json => "json",
+            EnvelopeFormat::Raw => "raw",
+        }
+    }
+}
+
+impl fmt::Display for EnvelopeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+fn parse_key_format(format: &str) -> Result<KeyFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for KeyFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(KeyFormat::Raw),
+            "pem" => Ok(KeyFormat::Pem),
+            "openssh" => Ok(KeyFormat::OpenSsh),
+            "jwk" => Ok(KeyFormat::Jwk),
+            _ => Err(anyhow::anyhow!("Invalid key format")),
+        }
+    }
+}
+
+impl From<KeyFormat> for &'static str {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Raw => "raw",
+            KeyFormat::Pem => "pem",
+            KeyFormat::OpenSsh => "openssh",
+            KeyFormat::Jwk => "jwk",
+        }
+    }
+}
+
+impl fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
 }
 
 fn parse_text_sign_format(format: &str) -> Result<TextSignFormat, anyhow::Error> {
@@ -65,6 +292,12 @@ impl FromStr for TextSignFormat {
         match s {
             "blake3" => Ok(TextSignFormat::Blake3),
             "ed25519" => Ok(TextSignFormat::Ed25519),
+            "ed25519ph" => Ok(TextSignFormat::Ed25519ph),
+            "hmac-sha256" => Ok(TextSignFormat::HmacSha256),
+            "hmac-sha512" => Ok(TextSignFormat::HmacSha512),
+            "ecdsa-p256" => Ok(TextSignFormat::EcdsaP256),
+            "ecdsa-secp256k1" => Ok(TextSignFormat::EcdsaSecp256k1),
+            "sshsig" => Ok(TextSignFormat::SshSig),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -75,6 +308,12 @@ impl From<TextSignFormat> for &'static str {
         match format {
             TextSignFormat::Blake3 => "blake3",
             TextSignFormat::Ed25519 => "ed25519",
+            TextSignFormat::Ed25519ph => "ed25519ph",
+            TextSignFormat::HmacSha256 => "hmac-sha256",
+            TextSignFormat::HmacSha512 => "hmac-sha512",
+            TextSignFormat::EcdsaP256 => "ecdsa-p256",
+            TextSignFormat::EcdsaSecp256k1 => "ecdsa-secp256k1",
+            TextSignFormat::SshSig => "sshsig",
         }
     }
 }
@@ -92,29 +331,115 @@ impl CmdExecutor for TextSubCommand {
             TextSubCommand::Sign(opts) => opts.execute().await,
             TextSubCommand::Verify(opts) => opts.execute().await,
             TextSubCommand::Generate(opts) => opts.execute().await,
+            TextSubCommand::SignManifest(opts) => opts.execute().await,
+            TextSubCommand::VerifyManifest(opts) => opts.execute().await,
+            TextSubCommand::Encrypt(opts) => opts.execute().await,
+            TextSubCommand::Decrypt(opts) => opts.execute().await,
         }
     }
 }
 
 impl CmdExecutor for TextSignOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = get_reader(&self.input)?;
-        let key = get_content(&self.key)?;
-        let sig = process_text_sign(&mut reader, &key, self.format)?;
-
-        let encoded = URL_SAFE_NO_PAD.encode(sig);
-        println!("{}", encoded);
+        let stdin = self.input.iter().any(|i| i == "-");
+        let key = read_key(&self.key, stdin)?;
+        if !self.detached {
+            anyhow::ensure!(
+                self.input.len() == 1,
+                "signing several inputs needs --detached"
+            );
+            let mut reader = get_reader(&self.input[0])?;
+            println!("{}", self.sign(&mut reader, &key)?);
+            return Ok(());
+        }
 
+        for input in &self.input {
+            anyhow::ensure!(input != "-", "--detached needs input files, not stdin");
+            let sig = self.sign(&mut get_reader(input)?, &key)?;
+            let path = format!("{}.sig", input);
+            fs::write(&path, sig + "\n").await?;
+            println!("{}", path);
+        }
         Ok(())
     }
 }
 
+impl TextSignOpts {
+    fn sign(&self, reader: &mut dyn Read, key: &[u8]) -> anyhow::Result<String> {
+        if let TextSignFormat::SshSig = self.format {
+            let armored = process_text_sign_sshsig(reader, key, &self.namespace)?;
+            return Ok(armored.trim_end().to_string());
+        }
+        if self.envelope == EnvelopeFormat::Raw {
+            let sig = process_text_sign(reader, key, self.format)?;
+            return Ok(URL_SAFE_NO_PAD.encode(sig));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let envelope =
+            process_text_sign_envelope(reader, key, self.format, now, self.comment.clone())?;
+        match self.envelope {
+            EnvelopeFormat::Json => envelope.to_json(),
+            _ => Ok(envelope.to_compact()),
+        }
+    }
+}
+
 impl CmdExecutor for TextVeriryOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let mut reader = get_reader(&self.input)?;
-        let key = get_content(&self.key)?;
-        let decoded = URL_SAFE_NO_PAD.decode(&self.sig)?;
-        let verified = process_text_verify(&mut reader, &key, &decoded, self.format)?;
+        // blake3 verifies with the secret key
+        let key = read_key(&self.key, self.input == "-")?;
+        let sig = match &self.sig {
+            Some(sig) if Path::new(sig).is_file() => fs::read_to_string(sig).await?,
+            Some(sig) => sig.clone(),
+            None if self.input != "-" => fs::read_to_string(format!("{}.sig", self.input)).await?,
+            None => anyhow::bail!("--sig is needed when the input is stdin"),
+        };
+        let sig = sig.trim();
+
+        if sig.starts_with("-----BEGIN SSH SIGNATURE-----") {
+            if let Some(format) = self.format.filter(|f| !matches!(f, TextSignFormat::SshSig)) {
+                anyhow::bail!("signature is sshsig, not {}", format);
+            }
+            let principals = process_text_verify_sshsig(
+                &mut reader,
+                &key,
+                sig.as_bytes(),
+                &self.namespace,
+                self.identity,
+            )?;
+            println!(
+                "signature verified: sshsig for namespace {}, signer {}",
+                self.namespace, principals
+            );
+            return Ok(());
+        }
+
+        if let Some(envelope) = SignatureEnvelope::parse(sig) {
+            let envelope = envelope?;
+            let format = envelope.format()?;
+            if let Some(expected) = self.format.filter(|f| f.to_string() != envelope.alg) {
+                anyhow::bail!("signature is {}, not {}", format, expected);
+            }
+            process_text_verify_envelope(&mut reader, &key, &envelope)?;
+
+            let signed_at = chrono::DateTime::from_timestamp(envelope.timestamp, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| envelope.timestamp.to_string());
+            println!(
+                "signature verified: {} key {}, signed {}",
+                envelope.alg, envelope.key_id, signed_at
+            );
+            if let Some(comment) = &envelope.comment {
+                println!("comment: {}", comment);
+            }
+            return Ok(());
+        }
+
+        let decoded = URL_SAFE_NO_PAD.decode(sig)?;
+        let format = self.format.unwrap_or(TextSignFormat::Blake3);
+        let verified = process_text_verify(&mut reader, &key, &decoded, format)?;
         if verified {
             println!("siganature verified");
         } else {
@@ -125,13 +450,178 @@ impl CmdExecutor for TextVeriryOpts {
     }
 }
 
+fn manifest_path(dir: &Path, manifest: Option<PathBuf>) -> PathBuf {
+    manifest.unwrap_or_else(|| dir.join("MANIFEST.b3"))
+}
+
+fn manifest_sig_path(manifest: &Path) -> PathBuf {
+    let mut path = manifest.as_os_str().to_owned();
+    path.push(".sig");
+    path.into()
+}
+
+impl CmdExecutor for TextSignManifestOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let key = read_key(&self.key, false)?;
+        let manifest_path = manifest_path(&self.dir, self.manifest);
+        let sig_path = manifest_sig_path(&manifest_path);
+        let exclude = [manifest_path.clone(), sig_path.clone()];
+
+        let manifest = process_manifest_build(&self.dir, &exclude)?;
+        let now = chrono::Utc::now().timestamp();
+        let envelope = process_text_sign_envelope(
+            &mut manifest.as_bytes(),
+            &key,
+            self.format,
+            now,
+            self.comment,
+        )?;
+        fs::write(&manifest_path, &manifest).await?;
+        fs::write(&sig_path, envelope.to_compact() + "\n").await?;
+
+        println!(
+            "signed {} files: {}, {}",
+            manifest.lines().count(),
+            manifest_path.display(),
+            sig_path.display()
+        );
+        Ok(())
+    }
+}
+
+impl CmdExecutor for TextVerifyManifestOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let key = read_key(&self.key, false)?;
+        let manifest_path = manifest_path(&self.dir, self.manifest);
+        let sig_path = manifest_sig_path(&manifest_path);
+        let manifest = fs::read_to_string(&manifest_path).await?;
+        let sig = fs::read_to_string(&sig_path).await?;
+
+        let envelope = SignatureEnvelope::parse(&sig).ok_or_else(|| {
+            anyhow::anyhow!("{} is not a signature envelope", sig_path.display())
+        })??;
+        process_text_verify_envelope(&mut manifest.as_bytes(), &key, &envelope)?;
+
+        let exclude = [manifest_path, sig_path];
+        let report = process_manifest_check(&self.dir, &manifest, &exclude)?;
+        for (label, paths) in [
+            ("missing", &report.missing),
+            ("extra", &report.extra),
+            ("modified", &report.modified),
+        ] {
+            for path in paths {
+                println!("{}: {}", label, path);
+            }
+        }
+        if !report.is_ok() {
+            anyhow::bail!(
+                "{} missing, {} extra, {} modified",
+                report.missing.len(),
+                report.extra.len(),
+                report.modified.len()
+            );
+        }
+        println!("manifest verified: {} files", manifest.lines().count());
+        Ok(())
+    }
+}
+
+impl CmdExecutor for TextEncryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut reader = get_reader(&self.input)?;
+        let sealed = match &self.key {
+            Some(key) => process_text_encrypt(&mut reader, &read_key(key, self.input == "-")?)?,
+            None => {
+                let password = read_password("Password: ", self.input == "-", true)?;
+                let params = PasswordKdfParams {
+                    m_cost: self.kdf_memory,
+                    t_cost: self.kdf_iterations,
+                    ..Default::default()
+                };
+                process_text_encrypt_password(&mut reader, &password, params)?
+            }
+        };
+
+        let mut writer = get_writer(&self.output)?;
+        if self.raw {
+            writer.write_all(&sealed)?;
+        } else {
+            writeln!(writer, "{}", URL_SAFE_NO_PAD.encode(sealed))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl CmdExecutor for TextDecryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut sealed = get_content(&self.input)?;
+        if !self.raw {
+            sealed = URL_SAFE_NO_PAD.decode(sealed.trim_ascii())?;
+        }
+        let plaintext = match &self.key {
+            Some(key) => process_text_decrypt(&sealed, &read_key(key, self.input == "-")?)?,
+            None => {
+                let password = read_password("Password: ", self.input == "-", false)?;
+                process_text_decrypt_password(&sealed, &password)?
+            }
+        };
+
+        let mut writer = get_writer(&self.output)?;
+        writer.write_all(&plaintext)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
 impl CmdExecutor for KeyGenerateOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mkey = process_text_key_generate(self.format)?;
-        for (k, v) in mkey {
-            fs::write(self.output_path.join(k), v).await?;
+        let mut mkey = process_text_key_generate(self.format, self.key_format)?;
+        let secret_name = secret_key_name(self.format);
+        if self.encrypt {
+            let passphrase = read_password("Passphrase: ", false, true)?;
+            let secret = &mkey[secret_name];
+            let sealed = process_key_encrypt(secret, &passphrase, PasswordKdfParams::default())?;
+            mkey.insert(secret_name, sealed.into_bytes());
+        }
+        for (k, v) in &mkey {
+            let path = self.output_path.join(k);
+            if *k == secret_name {
+                write_private_file(&path, v).await?;
+            } else {
+                fs::write(path, v).await?;
+            }
+        }
+
+        if self.clip.clip {
+            let secret = &mkey[secret_name];
+            // text keys are copied as is, binary ones as base64
+            let content = match std::str::from_utf8(secret) {
+                Ok(s) => s.to_string(),
+                Err(_) => URL_SAFE_NO_PAD.encode(secret),
+            };
+            let mut backend = clipboard_backend(self.clip.clip_backend);
+            process_clip(backend.as_mut(), content.as_bytes(), self.clip.timeout()).await?;
         }
 
         Ok(())
     }
 }
+
+/// Only the owner may read a private key, also when it replaces an existing file.
+async fn write_private_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+    file.write_all(data).await?;
+    file.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::{get_content, process_url_decode, process_url_encode, process_url_parse, CmdExecutor};
+use clap::Parser;
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+#[derive(Debug, Parser)]
+pub enum UrlSubCommand {
+    #[command(about = "percent-encode text for a part of a URL")]
+    Encode(UrlEncodeOpts),
+    #[command(about = "percent-decode text")]
+    Decode(UrlDecodeOpts),
+    #[command(about = "break a URL into its parts as JSON")]
+    Parse(UrlParseOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlEncodeOpts {
+    #[arg(default_value = "-", help = "the text, - to read it from stdin")]
+    pub input: String,
+    #[arg(long, value_parser = parse_url_component, default_value = "component", help = "component (everything but A-Z a-z 0-9 - _ . ~), path, query (a key or value) or fragment")]
+    pub component: UrlComponent,
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlDecodeOpts {
+    #[arg(default_value = "-", help = "the text, - to read it from stdin")]
+    pub input: String,
+    #[arg(long, help = "decode + as a space, as in form-encoded query strings")]
+    pub plus: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlParseOpts {
+    #[arg(default_value = "-", help = "the URL, - to read it from stdin")]
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlComponent {
+    Component,
+    Path,
+    Query,
+    Fragment,
+}
+
+fn parse_url_component(component: &str) -> Result<UrlComponent, anyhow::Error> {
+    component.parse()
+}
+
+impl FromStr for UrlComponent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "component" => Ok(UrlComponent::Component),
+            "path" => Ok(UrlComponent::Path),
+            "query" => Ok(UrlComponent::Query),
+            "fragment" => Ok(UrlComponent::Fragment),
+            _ => Err(anyhow::anyhow!("Invalid component")),
+        }
+    }
+}
+
+impl From<UrlComponent> for &'static str {
+    fn from(component: UrlComponent) -> Self {
+        match component {
+            UrlComponent::Component => "component",
+            UrlComponent::Path => "path",
+            UrlComponent::Query => "query",
+            UrlComponent::Fragment => "fragment",
+        }
+    }
+}
+
+impl fmt::Display for UrlComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// Inline text, or stdin for `-` without its trailing newline.
+fn read_input(input: &str) -> anyhow::Result<String> {
+    match input {
+        "-" => {
+            let content = String::from_utf8(get_content("-")?)?;
+            Ok(content.trim_end_matches(['\r', '\n']).to_string())
+        }
+        input => Ok(input.to_string()),
+    }
+}
+
+impl CmdExecutor for UrlEncodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let input = read_input(&self.input)?;
+        println!("{}", process_url_encode(&input, self.component));
+        Ok(())
+    }
+}
+
+impl CmdExecutor for UrlDecodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let input = read_input(&self.input)?;
+        // decoded text isn't necessarily UTF-8
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&process_url_decode(&input, self.plus))?;
+        stdout.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+impl CmdExecutor for UrlParseOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let parsed = process_url_parse(&read_input(&self.url)?)?;
+        println!("{}", serde_json::to_string_pretty(&parsed)?);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for UrlSubCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            UrlSubCommand::Encode(opts) => opts.execute().await,
+            UrlSubCommand::Decode(opts) => opts.execute().await,
+            UrlSubCommand::Parse(opts) => opts.execute().await,
+        }
+    }
+}
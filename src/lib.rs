@@ -3,8 +3,9 @@ mod process;
 mod utils;
 
 pub use cli::{
-    Base64Format, Base64SubCommand, HttpSubCommand, Opts, SubCommand, TextSignFormat,
-    TextSubCommand,
+    Base64DecodeFormat, Base64Format, Base64SubCommand, ClipBackend, HttpSubCommand, JwtAlg,
+    JwtSubCommand, KeyFormat, Opts, PwdKdf, SubCommand, TextSignFormat, TextSubCommand,
+    UrlComponent, UrlSubCommand,
 };
 pub use process::*;
 pub use utils::*;
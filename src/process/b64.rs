@@ -1,63 +1,584 @@
 use crate::Base64Format;
 use anyhow::Result;
 use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
-    Engine as _,
+    alphabet,
+    engine::{
+        general_purpose::{PAD, STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+        DecodePaddingMode, GeneralPurpose,
+    },
+    read::DecoderReader,
+    write::EncoderWriter,
+};
+use data_encoding::{Encoding, Specification, BASE32, HEXLOWER, HEXLOWER_PERMISSIVE, HEXUPPER};
+use std::{
+    io::{self, Read, Write},
+    sync::OnceLock,
 };
 
-use std::{fs::File, io::Read};
+// groups processed per read for the non base64 formats
+const CHUNK_GROUPS: usize = 4096;
+// chars `--format auto` looks at, a multiple of every group size so a full prefix
+// has no partial group
+const DETECT_PREFIX: usize = 8192;
 
-pub fn process_encode(input: &str, format: Base64Format) -> anyhow::Result<()> {
-    let mut reader = get_reader(input)?;
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
+enum Codec {
+    Base64(&'static GeneralPurpose),
+    Text(TextCodec),
+}
 
-    let encoded = match format {
-        Base64Format::Standard => STANDARD.encode(&buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(&buf),
-    };
+struct TextCodec {
+    encode: fn(&[u8]) -> String,
+    decode: fn(&[u8]) -> Result<Vec<u8>>,
+    // bytes per encoded group / chars per decoded group, None if the input can't be split
+    encode_group: Option<usize>,
+    decode_group: Option<usize>,
+}
+
+/// Encode in chunks, so the input never has to fit in memory. A non zero `wrap`
+/// breaks the output into lines of that many columns (64 for PEM, 76 for MIME).
+/// base58 is the exception, its groups span the whole input.
+pub fn process_encode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+    wrap: usize,
+) -> Result<()> {
+    let mut wrapper = LineWrapper::new(writer, wrap);
+    match codec(format) {
+        Codec::Base64(engine) => {
+            let mut encoder = EncoderWriter::new(&mut wrapper, engine);
+            io::copy(reader, &mut encoder)?;
+            encoder.finish()?;
+        }
+        Codec::Text(codec) => match codec.encode_group {
+            Some(group) => {
+                let mut buf = vec![0u8; group * CHUNK_GROUPS];
+                loop {
+                    let n = read_full(reader, &mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    wrapper.write_all((codec.encode)(&buf[..n]).as_bytes())?;
+                }
+            }
+            None => {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)?;
+                wrapper.write_all((codec.encode)(&buf).as_bytes())?;
+            }
+        },
+    }
+
+    wrapper.inner.write_all(b"\n")?;
+    wrapper.inner.flush()?;
+    Ok(())
+}
+
+/// Decoded bytes are written as is, so images and keys survive the round trip.
+/// Whitespace and line breaks in the input are skipped.
+pub fn process_decode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+) -> Result<()> {
+    let mut filter = SkipWhitespace { inner: reader };
+    match codec(format) {
+        Codec::Base64(engine) => {
+            let mut decoder = DecoderReader::new(&mut filter, engine);
+            io::copy(&mut decoder, writer)?;
+        }
+        Codec::Text(codec) => match codec.decode_group {
+            Some(group) => {
+                let mut buf = vec![0u8; group * CHUNK_GROUPS];
+                loop {
+                    let n = read_full(&mut filter, &mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    writer.write_all(&(codec.decode)(&buf[..n])?)?;
+                }
+            }
+            None => {
+                let mut buf = Vec::new();
+                filter.read_to_end(&mut buf)?;
+                writer.write_all(&(codec.decode)(&buf)?)?;
+            }
+        },
+    }
 
-    println!("{}", encoded);
+    writer.flush()?;
     Ok(())
 }
 
-pub fn process_decode(input: &str, format: Base64Format) -> anyhow::Result<()> {
-    let mut reader = get_reader(input)?;
+/// Decode input of unknown provenance, returning the format it was detected as.
+/// The format is detected from the first `DETECT_PREFIX` chars, the rest is streamed.
+pub fn process_decode_auto(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<Base64Format> {
+    let mut filter = SkipWhitespace { inner: reader };
+    let mut prefix = vec![0u8; DETECT_PREFIX];
+    let n = read_full(&mut filter, &mut prefix)?;
+    prefix.truncate(n);
+
+    let format = detect_format(&prefix)
+        .ok_or_else(|| anyhow::anyhow!("input is not base64, hex or base32"))?;
+    if n < DETECT_PREFIX {
+        // the prefix is the whole input, padding and length were checked on all of it
+        process_decode(&mut prefix.as_slice(), writer, format)?;
+        return Ok(format);
+    }
 
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
-    let buf = buf.trim();
+    let mut input = TailTracker {
+        inner: prefix.as_slice().chain(filter),
+        len: 0,
+        last: 0,
+    };
+    let url_safe = matches!(format, Base64Format::UrlSafe | Base64Format::UrlSafePad);
+    if !url_safe && !matches!(format, Base64Format::Standard | Base64Format::StandardNoPad) {
+        process_decode(&mut input, writer, format)?;
+        return Ok(format);
+    }
 
-    let decoded = match format {
-        Base64Format::Standard => STANDARD.decode(buf)?,
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf)?,
+    // whether the input is padded is only known at its end, so accept both
+    let config = PAD.with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    let alphabet = if url_safe {
+        &alphabet::URL_SAFE
+    } else {
+        &alphabet::STANDARD
     };
+    let engine = GeneralPurpose::new(alphabet, config);
+    io::copy(&mut DecoderReader::new(&mut input, &engine), writer)?;
+    writer.flush()?;
 
-    let decoded = String::from_utf8(decoded)?;
+    let padded = input.last == b'=' || input.len.is_multiple_of(4);
+    Ok(match (url_safe, padded) {
+        (true, true) => Base64Format::UrlSafePad,
+        (true, false) => Base64Format::UrlSafe,
+        (false, true) => Base64Format::Standard,
+        (false, false) => Base64Format::StandardNoPad,
+    })
+}
 
-    println!("{}", decoded);
+/// Guess the format from the alphabet, padding and length: the candidates are
+/// ordered from the narrowest alphabet to the widest, the first one that decodes wins.
+pub fn detect_format(input: &[u8]) -> Option<Base64Format> {
+    if input.is_empty() {
+        return None;
+    }
+    let padded = input.ends_with(b"=");
+    let body = input
+        .strip_suffix(b"==")
+        .or(input.strip_suffix(b"="))
+        .unwrap_or(input);
+    let all = |f: fn(&u8) -> bool| body.iter().all(f);
 
-    Ok(())
+    let mut candidates = Vec::new();
+    if !padded && input.len().is_multiple_of(2) && all(u8::is_ascii_hexdigit) {
+        candidates.push(Base64Format::Hex);
+    }
+    if input.len().is_multiple_of(8)
+        && input
+            .iter()
+            .all(|c| matches!(c, b'A'..=b'Z' | b'2'..=b'7' | b'='))
+    {
+        candidates.push(Base64Format::Base32);
+    }
+    let url_safe = body.iter().any(|c| matches!(c, b'-' | b'_'));
+    let standard = body.iter().any(|c| matches!(c, b'+' | b'/'));
+    match (url_safe, standard, padded || input.len().is_multiple_of(4)) {
+        (true, true, _) => {}
+        (true, false, true) => candidates.push(Base64Format::UrlSafePad),
+        (true, false, false) => candidates.push(Base64Format::UrlSafe),
+        (false, _, true) => candidates.push(Base64Format::Standard),
+        (false, _, false) => candidates.push(Base64Format::StandardNoPad),
+    }
+
+    candidates
+        .into_iter()
+        .find(|format| process_decode(&mut &input[..], &mut io::sink(), *format).is_ok())
 }
 
-fn get_reader(input: &str) -> Result<Box<dyn Read>> {
-    let reader: Box<dyn Read> = if input == "-" {
-        Box::new(std::io::stdin())
-    } else {
-        Box::new(File::open(input)?)
+fn codec(format: Base64Format) -> Codec {
+    let text = |encode, decode, encode_group, decode_group| {
+        Codec::Text(TextCodec {
+            encode,
+            decode,
+            encode_group,
+            decode_group,
+        })
     };
+    match format {
+        Base64Format::Standard => Codec::Base64(&STANDARD),
+        Base64Format::StandardNoPad => Codec::Base64(&STANDARD_NO_PAD),
+        Base64Format::UrlSafe => Codec::Base64(&URL_SAFE_NO_PAD),
+        Base64Format::UrlSafePad => Codec::Base64(&URL_SAFE),
+        Base64Format::Hex => text(
+            |b| HEXLOWER.encode(b),
+            |s| Ok(HEXLOWER_PERMISSIVE.decode(s)?),
+            Some(1),
+            Some(2),
+        ),
+        Base64Format::HexUpper => text(
+            |b| HEXUPPER.encode(b),
+            |s| Ok(HEXLOWER_PERMISSIVE.decode(s)?),
+            Some(1),
+            Some(2),
+        ),
+        Base64Format::Base32 => text(
+            |b| BASE32.encode(b),
+            |s| Ok(BASE32.decode(s)?),
+            Some(5),
+            Some(8),
+        ),
+        Base64Format::Crockford => text(
+            |b| crockford().encode(b),
+            |s| Ok(crockford().decode(s)?),
+            Some(5),
+            Some(8),
+        ),
+        Base64Format::Base58 => text(
+            |b| bs58::encode(b).into_string(),
+            |s| Ok(bs58::decode(s).into_vec()?),
+            None,
+            None,
+        ),
+        // 'z' abbreviates a zero group, so decoding can't be split on char counts
+        Base64Format::Ascii85 => text(ascii85_encode, ascii85_decode, Some(4), None),
+        Base64Format::Z85 => text(
+            |b| z85::encode(b),
+            |s| z85::decode(s).map_err(|e| anyhow::anyhow!("invalid z85: {:?}", e)),
+            Some(4),
+            Some(5),
+        ),
+    }
+}
+
+/// Crockford's base32: no padding, decoding ignores case and reads I/L as 1 and O as 0.
+fn crockford() -> &'static Encoding {
+    static CROCKFORD: OnceLock<Encoding> = OnceLock::new();
+    CROCKFORD.get_or_init(|| {
+        let mut spec = Specification::new();
+        spec.symbols.push_str("0123456789ABCDEFGHJKMNPQRSTVWXYZ");
+        spec.translate.from.push_str("abcdefghjkmnpqrstvwxyziIlLoO");
+        spec.translate.to.push_str("ABCDEFGHJKMNPQRSTVWXYZ111100");
+        spec.encoding().expect("valid crockford spec")
+    })
+}
 
-    Ok(reader)
+fn ascii85_encode(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len() / 4 * 5 + 5);
+    for chunk in input.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        if value == 0 && chunk.len() == 4 {
+            out.push('z');
+            continue;
+        }
+        let mut digits = [0u8; 5];
+        for d in digits.iter_mut().rev() {
+            *d = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        // a partial group of n bytes is written as n + 1 chars
+        out.extend(digits[..chunk.len() + 1].iter().map(|d| *d as char));
+    }
+    out
+}
+
+fn ascii85_decode(input: &[u8]) -> Result<Vec<u8>> {
+    let input = input.strip_prefix(b"<~").unwrap_or(input);
+    let input = input.strip_suffix(b"~>").unwrap_or(input);
+
+    let mut out = Vec::with_capacity(input.len() / 5 * 4 + 4);
+    let mut group = Vec::with_capacity(5);
+    for &c in input {
+        match c {
+            b'z' if group.is_empty() => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(c - b'!');
+                if group.len() == 5 {
+                    out.extend_from_slice(&ascii85_group(&group)?);
+                    group.clear();
+                }
+            }
+            _ => anyhow::bail!("invalid ascii85 char {:?}", c as char),
+        }
+    }
+    if !group.is_empty() {
+        anyhow::ensure!(group.len() > 1, "invalid ascii85 length");
+        let n = group.len() - 1;
+        // pad with the highest digit so truncating gives back the original bytes
+        group.resize(5, 84);
+        out.extend_from_slice(&ascii85_group(&group)?[..n]);
+    }
+    Ok(out)
+}
+
+fn ascii85_group(digits: &[u8]) -> Result<[u8; 4]> {
+    let value = digits
+        .iter()
+        .try_fold(0u32, |acc, d| acc.checked_mul(85)?.checked_add(*d as u32))
+        .ok_or_else(|| anyhow::anyhow!("invalid ascii85 group"))?;
+    Ok(value.to_be_bytes())
+}
+
+// like read_exact, but a short read at the end of the input is fine
+pub(crate) fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+struct LineWrapper<W: Write> {
+    inner: W,
+    width: usize,
+    col: usize,
+}
+
+impl<W: Write> LineWrapper<W> {
+    fn new(inner: W, width: usize) -> Self {
+        Self {
+            inner,
+            width,
+            col: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for LineWrapper<W> {
+    // always consumes the whole buffer, EncoderWriter turns short writes into errors
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.width == 0 {
+            self.inner.write_all(buf)?;
+            return Ok(buf.len());
+        }
+        let mut rest = buf;
+        while !rest.is_empty() {
+            // the line break is written lazily, so there is never one before the final newline
+            if self.col == self.width {
+                self.inner.write_all(b"\n")?;
+                self.col = 0;
+            }
+            let n = rest.len().min(self.width - self.col);
+            self.inner.write_all(&rest[..n])?;
+            self.col += n;
+            rest = &rest[n..];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct SkipWhitespace<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut kept = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[kept] = buf[i];
+                    kept += 1;
+                }
+            }
+            // a chunk of only whitespace is not the end of the input
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+// counts the chars read and keeps the last one, to tell the padding once decoded
+struct TailTracker<R: Read> {
+    inner: R,
+    len: usize,
+    last: u8,
+}
+
+impl<R: Read> Read for TailTracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.len += n;
+            self.last = buf[n - 1];
+        }
+        Ok(n)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::get_reader;
+
+    const ALL_FORMATS: [Base64Format; 11] = [
+        Base64Format::Standard,
+        Base64Format::StandardNoPad,
+        Base64Format::UrlSafe,
+        Base64Format::UrlSafePad,
+        Base64Format::Hex,
+        Base64Format::HexUpper,
+        Base64Format::Base32,
+        Base64Format::Crockford,
+        Base64Format::Base58,
+        Base64Format::Ascii85,
+        Base64Format::Z85,
+    ];
+
+    fn encode(data: &[u8], format: Base64Format) -> Result<String> {
+        let mut encoded = Vec::new();
+        process_encode(&mut &data[..], &mut encoded, format, 0)?;
+        Ok(String::from_utf8(encoded)?.trim_end().to_string())
+    }
 
     #[test]
-    fn test_process_decode() {
-        let input = "fixtures/b64.txt";
+    fn test_process_decode() -> Result<()> {
+        let mut reader = get_reader("fixtures/b64.txt")?;
         let format = Base64Format::UrlSafe;
-        process_decode(input, format).unwrap();
+        let mut decoded = Vec::new();
+        process_decode(&mut reader, &mut decoded, format)?;
+        assert!(decoded.starts_with(b"[package]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_round_trip() -> Result<()> {
+        // every partial final group, and lengths around the chunk sizes of the
+        // 3, 4 and 5 byte groups and of the 64 KiB reads
+        let boundaries = [
+            3 * CHUNK_GROUPS,
+            4 * CHUNK_GROUPS,
+            5 * CHUNK_GROUPS,
+            crate::process::text::CHUNK_SIZE,
+        ];
+        let lens: Vec<usize> = (0..=9)
+            .chain(boundaries.iter().flat_map(|&b| b - 3..=b + 3))
+            .collect();
+        for format in ALL_FORMATS {
+            for &len in &lens {
+                // base58 is quadratic and has no chunks to cross
+                if format == Base64Format::Base58 && len > 9 {
+                    continue;
+                }
+                let data: Vec<u8> = (0..=255).cycle().take(len).collect();
+                for wrap in [0, 64, 76] {
+                    let mut encoded = Vec::new();
+                    process_encode(&mut data.as_slice(), &mut encoded, format, wrap)?;
+                    let text = String::from_utf8(encoded.clone())?;
+                    if wrap > 0 {
+                        assert!(text.lines().all(|l| l.len() <= wrap));
+                        if text.trim_end().len() > wrap {
+                            assert_eq!(text.lines().next().map(str::len), Some(wrap));
+                        }
+                    }
+                    let mut decoded = Vec::new();
+                    process_decode(&mut encoded.as_slice(), &mut decoded, format)?;
+                    assert_eq!(decoded, data, "{} len {} wrap {}", format, len, wrap);
+                }
+            }
+        }
+
+        // a lone z85 char can't be a partial group
+        let mut decoded = Vec::new();
+        assert!(process_decode(&mut &b"0"[..], &mut decoded, Base64Format::Z85).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_known_encodings() -> Result<()> {
+        let cases = [
+            (Base64Format::Standard, "Zm9vYmE="),
+            (Base64Format::StandardNoPad, "Zm9vYmE"),
+            (Base64Format::UrlSafePad, "Zm9vYmE="),
+            (Base64Format::Hex, "666f6f6261"),
+            (Base64Format::HexUpper, "666F6F6261"),
+            (Base64Format::Base32, "MZXW6YTB"),
+            (Base64Format::Crockford, "CSQPYRK1"),
+            (Base64Format::Base58, "CZJRhmz"),
+            (Base64Format::Ascii85, "AoDTs@/"),
+        ];
+        for (format, expected) in cases {
+            assert_eq!(encode(b"fooba", format)?, expected, "{}", format);
+        }
+        assert_eq!(
+            encode(b"\x86\x4F\xD2\x6F\xB5\x59\xF7\x5B", Base64Format::Z85)?,
+            "HelloWorld"
+        );
+        assert_eq!(encode(&[0; 4], Base64Format::Ascii85)?, "z");
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_format() -> Result<()> {
+        let cases = [
+            ("deadbeef", Base64Format::Hex),
+            ("DEADBEEF", Base64Format::Hex),
+            ("NBSWY3DP", Base64Format::Base32),
+            ("NBSWY3DPEE======", Base64Format::Base32),
+            ("aGVsbG8=", Base64Format::Standard),
+            ("aGVsbG8", Base64Format::StandardNoPad),
+            ("-_8=", Base64Format::UrlSafePad),
+            ("eyJhbGciOiJIUzI1NiJ9", Base64Format::Standard),
+            ("a-_8", Base64Format::UrlSafePad),
+            ("-_-_AQ", Base64Format::UrlSafe),
+            ("+/8=", Base64Format::Standard),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(detect_format(input.as_bytes()), Some(expected), "{}", input);
+        }
+        assert_eq!(detect_format(b"not base64!"), None);
+        assert_eq!(detect_format(b"a-+8"), None);
+
+        let mut decoded = Vec::new();
+        let format = process_decode_auto(&mut "aGVs\nbG8".as_bytes(), &mut decoded)?;
+        assert_eq!(format, Base64Format::StandardNoPad);
+        assert_eq!(decoded, b"hello");
+
+        // longer than the detection prefix, the padding is only seen at the end
+        let data: Vec<u8> = (0..=255).cycle().take(20_000).collect();
+        for (format, len) in [
+            (Base64Format::Hex, 20_000),
+            (Base64Format::Base32, 20_000),
+            (Base64Format::UrlSafePad, 20_000),
+            (Base64Format::UrlSafe, 20_000),
+            (Base64Format::Standard, 20_000),
+            (Base64Format::StandardNoPad, 19_999),
+        ] {
+            let mut encoded = Vec::new();
+            process_encode(&mut &data[..len], &mut encoded, format, 76)?;
+            let mut decoded = Vec::new();
+            let detected = process_decode_auto(&mut encoded.as_slice(), &mut decoded)?;
+            assert_eq!(detected, format);
+            assert_eq!(decoded, data[..len]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_skips_whitespace() -> Result<()> {
+        let mut decoded = Vec::new();
+        process_decode(
+            &mut "aGVs\r\n bG8g\n\td29y\nbGQ=\n".as_bytes(),
+            &mut decoded,
+            Base64Format::Standard,
+        )?;
+        assert_eq!(decoded, b"hello world");
+        Ok(())
     }
 }
@@ -0,0 +1,165 @@
+use crate::ClipBackend;
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    process::{Command, Stdio},
+    time::Duration,
+};
+use tracing::warn;
+
+pub trait ClipboardBackend {
+    fn copy(&mut self, content: &[u8]) -> Result<()>;
+
+    fn clear(&mut self) -> Result<()> {
+        self.copy(b"")
+    }
+}
+
+/// OSC 52 escape sequence, the terminal (even over ssh) sets the clipboard.
+pub struct Osc52<W: Write> {
+    out: W,
+}
+
+/// Pipe the content into a clipboard tool like xclip or wl-copy.
+pub struct CommandBackend {
+    program: &'static str,
+    args: &'static [&'static str],
+}
+
+impl<W: Write> Osc52<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl Osc52<Box<dyn Write>> {
+    /// Write to the controlling terminal so redirecting stdout doesn't swallow it.
+    pub fn terminal() -> Self {
+        let out: Box<dyn Write> = match OpenOptions::new().write(true).open("/dev/tty") {
+            Ok(tty) => Box::new(tty),
+            Err(_) => Box::new(std::io::stderr()),
+        };
+        Self::new(out)
+    }
+}
+
+impl<W: Write> ClipboardBackend for Osc52<W> {
+    fn copy(&mut self, content: &[u8]) -> Result<()> {
+        write!(self.out, "\x1b]52;c;{}\x07", STANDARD.encode(content))?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        // anything that is not base64 clears the selection
+        write!(self.out, "\x1b]52;c;!\x07")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl CommandBackend {
+    pub fn xclip() -> Self {
+        Self {
+            program: "xclip",
+            args: &["-selection", "clipboard"],
+        }
+    }
+
+    pub fn wl_copy() -> Self {
+        Self {
+            program: "wl-copy",
+            args: &[],
+        }
+    }
+
+    pub fn pbcopy() -> Self {
+        Self {
+            program: "pbcopy",
+            args: &[],
+        }
+    }
+}
+
+impl ClipboardBackend for CommandBackend {
+    fn copy(&mut self, content: &[u8]) -> Result<()> {
+        let mut child = Command::new(self.program)
+            .args(self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("failed to run {}: {}", self.program, e))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(content)?;
+        let status = child.wait()?;
+        anyhow::ensure!(status.success(), "{} exited with {}", self.program, status);
+        Ok(())
+    }
+}
+
+pub fn clipboard_backend(backend: ClipBackend) -> Box<dyn ClipboardBackend> {
+    match backend {
+        ClipBackend::Auto => {
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                Box::new(CommandBackend::wl_copy())
+            } else if std::env::var_os("DISPLAY").is_some() {
+                Box::new(CommandBackend::xclip())
+            } else if cfg!(target_os = "macos") {
+                Box::new(CommandBackend::pbcopy())
+            } else {
+                Box::new(Osc52::terminal())
+            }
+        }
+        ClipBackend::Osc52 => Box::new(Osc52::terminal()),
+        ClipBackend::Xclip => Box::new(CommandBackend::xclip()),
+        ClipBackend::WlCopy => Box::new(CommandBackend::wl_copy()),
+        ClipBackend::Pbcopy => Box::new(CommandBackend::pbcopy()),
+    }
+}
+
+/// Copy `content`, then clear the clipboard after `timeout` (or on ctrl-c).
+/// A zero timeout leaves the content on the clipboard.
+pub async fn process_clip(
+    backend: &mut dyn ClipboardBackend,
+    content: &[u8],
+    timeout: Duration,
+) -> Result<()> {
+    backend.copy(content)?;
+    if timeout.is_zero() {
+        eprintln!("copied to clipboard");
+        return Ok(());
+    }
+
+    eprintln!(
+        "copied to clipboard, clearing in {} seconds",
+        timeout.as_secs()
+    );
+    tokio::select! {
+        _ = tokio::time::sleep(timeout) => {}
+        ret = tokio::signal::ctrl_c() => {
+            if let Err(e) = ret {
+                warn!("failed to listen for ctrl-c: {:?}", e);
+            }
+        }
+    }
+    backend.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_osc52_copy_and_clear() -> Result<()> {
+        let mut out = Vec::new();
+        let mut backend = Osc52::new(&mut out);
+        process_clip(&mut backend, b"hello", Duration::from_millis(10)).await?;
+        assert_eq!(out, b"\x1b]52;c;aGVsbG8=\x07\x1b]52;c;!\x07");
+        Ok(())
+    }
+}
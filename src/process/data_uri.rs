@@ -0,0 +1,157 @@
+use crate::{process_decode, process_encode, Base64Format};
+use anyhow::Result;
+use percent_encoding::percent_decode;
+use std::io::{Read, Write};
+
+// enough for every magic number and to tell text from binary
+const SNIFF_LEN: usize = 512;
+// longest `data:<mime>;<params>;base64,` header accepted
+const MAX_HEADER_LEN: usize = 1024;
+
+/// Write the input as `data:<mime>;base64,...`, the mime type is sniffed from the
+/// first bytes and the rest is streamed.
+pub fn process_encode_data_uri(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> Result<&'static str> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    reader.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    let mime = sniff_mime(&head);
+
+    write!(writer, "data:{};base64,", mime)?;
+    process_encode(
+        &mut head.as_slice().chain(reader),
+        writer,
+        Base64Format::Standard,
+        0,
+    )?;
+    Ok(mime)
+}
+
+/// Strip the `data:` header and write the payload, returning the declared mime type.
+pub fn process_decode_data_uri(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<String> {
+    // read byte by byte so nothing past the comma is consumed
+    let mut header = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == b',' {
+            break;
+        }
+        header.push(byte[0]);
+        anyhow::ensure!(
+            header.len() <= MAX_HEADER_LEN,
+            "data uri header is too long"
+        );
+    }
+
+    let header = String::from_utf8(header)?;
+    let header = header
+        .trim_start()
+        .strip_prefix("data:")
+        .ok_or_else(|| anyhow::anyhow!("input is not a data uri"))?;
+    let (mime, base64) = match header.strip_suffix(";base64") {
+        Some(mime) => (mime, true),
+        None => (header, false),
+    };
+    // RFC 2397: an empty mime type means text/plain
+    let mime = if mime.is_empty() || mime.starts_with(';') {
+        format!("text/plain{}", mime)
+    } else {
+        mime.to_string()
+    };
+
+    if base64 {
+        process_decode(reader, writer, Base64Format::Standard)?;
+    } else {
+        let mut payload = Vec::new();
+        reader.read_to_end(&mut payload)?;
+        // like browsers, a `%` not followed by two hex digits is kept as is
+        let decoded: Vec<u8> = percent_decode(payload.trim_ascii_end()).collect();
+        writer.write_all(&decoded)?;
+        writer.flush()?;
+    }
+    Ok(mime)
+}
+
+pub fn sniff_mime(head: &[u8]) -> &'static str {
+    const MAGIC: [(&[u8], &str); 6] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"\xef\xbb\xbf", "text/plain;charset=utf-8"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+
+    // a multi-byte char may be cut at the end of the sniffed bytes
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).expect("valid up to here")
+        }
+        Err(_) => return "application/octet-stream",
+    };
+    if text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+    {
+        return "application/octet-stream";
+    }
+
+    let trimmed = text.trim_start();
+    if trimmed.starts_with("<svg") || (trimmed.starts_with("<?xml") && trimmed.contains("<svg")) {
+        "image/svg+xml"
+    } else if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        "application/json"
+    } else {
+        "text/plain;charset=utf-8"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_mime() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff_mime(b"\xff\xd8\xff\xe0\0\x10JFIF"), "image/jpeg");
+        assert_eq!(sniff_mime(b"GIF89a\x01\0"), "image/gif");
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(
+            sniff_mime(b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>"),
+            "image/svg+xml"
+        );
+        assert_eq!(sniff_mime(b"  {\"a\": 1}"), "application/json");
+        assert_eq!(sniff_mime("héllo".as_bytes()), "text/plain;charset=utf-8");
+        assert_eq!(sniff_mime(b"\0\x01\x02"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_data_uri_round_trip() -> Result<()> {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff\x00";
+        let mut uri = Vec::new();
+        let mime = process_encode_data_uri(&mut &png[..], &mut uri)?;
+        assert_eq!(mime, "image/png");
+        assert!(uri.starts_with(b"data:image/png;base64,iVBORw0KGgo"));
+
+        let mut decoded = Vec::new();
+        let mime = process_decode_data_uri(&mut uri.as_slice(), &mut decoded)?;
+        assert_eq!(mime, "image/png");
+        assert_eq!(decoded, png);
+
+        let mut decoded = Vec::new();
+        let mime = process_decode_data_uri(&mut &b"data:,a%20b\n"[..], &mut decoded)?;
+        assert_eq!(mime, "text/plain");
+        assert_eq!(decoded, b"a b");
+
+        let mut decoded = Vec::new();
+        process_decode_data_uri(&mut &b"data:,100%25%zz"[..], &mut decoded)?;
+        assert_eq!(decoded, b"100%%zz");
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use crate::{
+    process_decode, process_decode_auto, process_encode, Base64DecodeFormat, Base64Format,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+use serde_json::Value as JsonValue;
+use serde_yaml::Value as YamlValue;
+use std::{
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+/// A JSONPath subset (`$.data.*`, `$.items[0].value`, `$['a.b']`) or a plain
+/// dotted path (`data.password`) selecting values inside a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocPath(Vec<PathSegment>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Base64 encode the string values selected by `paths`, the document is re-emitted
+/// in the format it was read in.
+pub fn process_encode_doc(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    paths: &[DocPath],
+    format: Base64Format,
+) -> Result<()> {
+    transform_doc(reader, writer, paths, |value| {
+        let mut buf = Vec::new();
+        process_encode(&mut value.as_bytes(), &mut buf, format, 0)?;
+        Ok(String::from_utf8(buf)?.trim_end().to_string())
+    })
+}
+
+/// Decode the string values selected by `paths`, every decoded value has to be UTF-8
+/// so it can be put back into the document.
+pub fn process_decode_doc(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    paths: &[DocPath],
+    format: Base64DecodeFormat,
+) -> Result<()> {
+    transform_doc(reader, writer, paths, |value| {
+        let mut buf = Vec::new();
+        match format {
+            Base64DecodeFormat::Format(format) => {
+                process_decode(&mut value.as_bytes(), &mut buf, format)?
+            }
+            Base64DecodeFormat::Auto => {
+                process_decode_auto(&mut value.as_bytes(), &mut buf)?;
+            }
+        }
+        String::from_utf8(buf).context("decoded value is not valid UTF-8")
+    })
+}
+
+fn transform_doc(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    paths: &[DocPath],
+    mut f: impl FnMut(&str) -> Result<String>,
+) -> Result<()> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+
+    let mut count = 0;
+    let mut transform = |s: &mut String, location: &str| -> Result<()> {
+        *s = f(s).with_context(|| format!("at {}", location))?;
+        count += 1;
+        Ok(())
+    };
+
+    // JSON goes through serde_json so numbers keep their exact text, anything else is
+    // YAML, whose stream may hold several `---` separated documents
+    if input.trim_start().starts_with(['{', '[']) {
+        let mut doc: JsonValue = serde_json::from_str(&input).context("invalid JSON document")?;
+        for path in paths {
+            path.apply(&mut doc, &mut transform)?;
+        }
+        anyhow::ensure!(count > 0, "no string value matches the given path");
+        write_json(&mut *writer, &doc, json_indent(&input))?;
+    } else {
+        let mut docs = serde_yaml::Deserializer::from_str(&input)
+            .enumerate()
+            .map(|(i, doc)| {
+                YamlValue::deserialize(doc).with_context(|| format!("invalid document {}", i + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for doc in docs.iter_mut() {
+            for path in paths {
+                path.apply(doc, &mut transform)?;
+            }
+        }
+        anyhow::ensure!(count > 0, "no string value matches the given path");
+        for (i, doc) in docs.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b"---\n")?;
+            }
+            serde_yaml::to_writer(&mut *writer, doc)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// the indent of the first indented line, None for JSON written on a single line
+fn json_indent(input: &str) -> Option<&str> {
+    let mut lines = input.trim().lines().skip(1);
+    let line = lines.find(|l| !l.trim().is_empty())?;
+    let indent = &line[..line.len() - line.trim_start().len()];
+    Some(if indent.is_empty() { "  " } else { indent })
+}
+
+fn write_json(writer: &mut dyn Write, doc: &JsonValue, indent: Option<&str>) -> Result<()> {
+    match indent {
+        Some(indent) => {
+            let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+            let mut ser = serde_json::Serializer::with_formatter(&mut *writer, formatter);
+            doc.serialize(&mut ser)?;
+        }
+        None => serde_json::to_writer(&mut *writer, doc)?,
+    }
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// A JSON or YAML tree a `DocPath` can walk.
+trait DocNode {
+    fn key_mut(&mut self, key: &str) -> Option<&mut Self>;
+    fn index_mut(&mut self, i: usize) -> Option<&mut Self>;
+    /// Every child with its location segment, e.g. `['a']` or `[0]`.
+    fn children_mut(&mut self) -> Vec<(String, &mut Self)>;
+    fn string_mut(&mut self) -> Option<&mut String>;
+}
+
+impl DocNode for JsonValue {
+    fn key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_object_mut()?.get_mut(key)
+    }
+
+    fn index_mut(&mut self, i: usize) -> Option<&mut Self> {
+        self.as_array_mut()?.get_mut(i)
+    }
+
+    fn children_mut(&mut self) -> Vec<(String, &mut Self)> {
+        match self {
+            JsonValue::Object(map) => map
+                .iter_mut()
+                .map(|(key, child)| (format!("['{}']", key), child))
+                .collect(),
+            JsonValue::Array(seq) => seq
+                .iter_mut()
+                .enumerate()
+                .map(|(i, child)| (format!("[{}]", i), child))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn string_mut(&mut self) -> Option<&mut String> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl DocNode for YamlValue {
+    fn key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            YamlValue::Mapping(map) => map.get_mut(key),
+            YamlValue::Tagged(tagged) => tagged.value.key_mut(key),
+            _ => None,
+        }
+    }
+
+    fn index_mut(&mut self, i: usize) -> Option<&mut Self> {
+        match self {
+            YamlValue::Sequence(seq) => seq.get_mut(i),
+            YamlValue::Tagged(tagged) => tagged.value.index_mut(i),
+            _ => None,
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<(String, &mut Self)> {
+        match self {
+            YamlValue::Mapping(map) => map
+                .iter_mut()
+                .map(|(key, child)| match key {
+                    YamlValue::String(key) => (format!("['{}']", key), child),
+                    key => (format!("[{:?}]", key), child),
+                })
+                .collect(),
+            YamlValue::Sequence(seq) => seq
+                .iter_mut()
+                .enumerate()
+                .map(|(i, child)| (format!("[{}]", i), child))
+                .collect(),
+            YamlValue::Tagged(tagged) => tagged.value.children_mut(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn string_mut(&mut self) -> Option<&mut String> {
+        match self {
+            YamlValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl DocPath {
+    fn apply<N: DocNode>(
+        &self,
+        doc: &mut N,
+        f: &mut dyn FnMut(&mut String, &str) -> Result<()>,
+    ) -> Result<()> {
+        visit(doc, &self.0, &mut "$".to_string(), f)
+    }
+}
+
+fn visit<N: DocNode>(
+    value: &mut N,
+    segments: &[PathSegment],
+    location: &mut String,
+    f: &mut dyn FnMut(&mut String, &str) -> Result<()>,
+) -> Result<()> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return match value.string_mut() {
+            Some(s) => f(s, location),
+            None => Ok(()),
+        };
+    };
+
+    let len = location.len();
+    match segment {
+        PathSegment::Key(key) => {
+            if let Some(child) = value.key_mut(key) {
+                location.push_str(&format!("['{}']", key));
+                visit(child, rest, location, f)?;
+            }
+        }
+        PathSegment::Index(i) => {
+            if let Some(child) = value.index_mut(*i) {
+                location.push_str(&format!("[{}]", i));
+                visit(child, rest, location, f)?;
+            }
+        }
+        PathSegment::Wildcard => {
+            for (segment, child) in value.children_mut() {
+                location.push_str(&segment);
+                visit(child, rest, location, f)?;
+                location.truncate(len);
+            }
+        }
+    }
+    location.truncate(len);
+    Ok(())
+}
+
+impl FromStr for DocPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `a.b` is short for `$.a.b`
+        let dotted = format!(".{}", s);
+        let mut rest = match s.strip_prefix('$') {
+            Some(rest) => rest,
+            None if s.starts_with('[') => s,
+            None => &dotted,
+        };
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                anyhow::ensure!(
+                    !after.starts_with('.'),
+                    "recursive descent is not supported"
+                );
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                let key = &after[..end];
+                anyhow::ensure!(!key.is_empty(), "empty key in path {:?}", s);
+                segments.push(match key {
+                    "*" => PathSegment::Wildcard,
+                    key => PathSegment::Key(key.to_string()),
+                });
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after
+                    .find(']')
+                    .ok_or_else(|| anyhow::anyhow!("unclosed [ in path {:?}", s))?;
+                let inner = after[..end].trim();
+                segments.push(match inner {
+                    "*" => PathSegment::Wildcard,
+                    _ if inner.len() >= 2
+                        && (inner.starts_with('\'') && inner.ends_with('\'')
+                            || inner.starts_with('"') && inner.ends_with('"')) =>
+                    {
+                        PathSegment::Key(inner[1..inner.len() - 1].to_string())
+                    }
+                    _ => {
+                        PathSegment::Index(inner.parse().with_context(|| {
+                            format!("invalid index {:?} in path {:?}", inner, s)
+                        })?)
+                    }
+                });
+                rest = &after[end + 1..];
+            } else {
+                anyhow::bail!("unexpected {:?} in path {:?}", rest, s);
+            }
+        }
+        Ok(DocPath(segments))
+    }
+}
+
+impl fmt::Display for DocPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Key(key) => write!(f, "['{}']", key)?,
+                PathSegment::Index(i) => write!(f, "[{}]", i)?,
+                PathSegment::Wildcard => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_doc_path() -> Result<()> {
+        use PathSegment::*;
+        let expected = DocPath(vec![Key("data".into()), Wildcard]);
+        assert_eq!("$.data.*".parse::<DocPath>()?, expected);
+        assert_eq!("data.*".parse::<DocPath>()?, expected);
+        assert_eq!("$['data'][*]".parse::<DocPath>()?, expected);
+        assert_eq!(
+            "$.items[0][\"a.b\"]".parse::<DocPath>()?,
+            DocPath(vec![Key("items".into()), Index(0), Key("a.b".into())])
+        );
+        assert!("$..data".parse::<DocPath>().is_err());
+        assert!("$.items[x]".parse::<DocPath>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_yaml_secret() -> Result<()> {
+        let secret = "apiVersion: v1\nkind: Secret\ndata:\n  username: YWRtaW4=\n  password: MWYyZDFlMmU2N2Rm\n";
+        let mut out = Vec::new();
+        let paths = ["$.data.*".parse()?];
+        let format = Base64DecodeFormat::Format(Base64Format::Standard);
+        process_decode_doc(&mut secret.as_bytes(), &mut out, &paths, format)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "apiVersion: v1\nkind: Secret\ndata:\n  username: admin\n  password: 1f2d1e2e67df\n"
+        );
+
+        let stream = "data:\n  a: aGk=\n---\ndata:\n  a: eA==\n";
+        let mut out = Vec::new();
+        process_decode_doc(&mut stream.as_bytes(), &mut out, &paths, format)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "data:\n  a: hi\n---\ndata:\n  a: x\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_json_keeps_order_and_format() -> Result<()> {
+        let doc = r#"{"z": {"token": "hi"}, "a": [1, "x"]}"#;
+        let mut out = Vec::new();
+        let paths = ["z.token".parse()?, "$.a[*]".parse()?];
+        process_encode_doc(
+            &mut doc.as_bytes(),
+            &mut out,
+            &paths,
+            Base64Format::Standard,
+        )?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "{\"z\":{\"token\":\"aGk=\"},\"a\":[1,\"eA==\"]}\n"
+        );
+
+        let pretty = "{\n    \"z\": {\n        \"token\": \"hi\"\n    }\n}\n";
+        let mut out = Vec::new();
+        let paths = ["z.token".parse()?];
+        process_encode_doc(
+            &mut pretty.as_bytes(),
+            &mut out,
+            &paths,
+            Base64Format::Standard,
+        )?;
+        assert_eq!(String::from_utf8(out)?, pretty.replace("hi", "aGk="));
+
+        // numbers nothing selected come back byte for byte
+        let doc = r#"{"n": 1e5, "f": 1.50, "big": 12345678901234567890123, "s": "hi"}"#;
+        let mut out = Vec::new();
+        let paths = ["s".parse()?];
+        process_encode_doc(
+            &mut doc.as_bytes(),
+            &mut out,
+            &paths,
+            Base64Format::Standard,
+        )?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "{\"n\":1e5,\"f\":1.50,\"big\":12345678901234567890123,\"s\":\"aGk=\"}\n"
+        );
+
+        let mut out = Vec::new();
+        let paths = ["$.missing".parse()?];
+        assert!(process_encode_doc(
+            &mut doc.as_bytes(),
+            &mut out,
+            &paths,
+            Base64Format::Standard
+        )
+        .is_err());
+        Ok(())
+    }
+}
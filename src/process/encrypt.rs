@@ -0,0 +1,339 @@
+use crate::load_blake3_key;
+use anyhow::Result;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::rngs::OsRng;
+use std::io::Read;
+use thiserror::Error;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const SALT_LEN: usize = 16;
+
+/// Version byte of the password container:
+/// `version | kdf | m_cost (u32 BE) | t_cost (u32 BE) | p_cost (u32 BE) | salt | nonce | ciphertext`.
+/// Everything before the ciphertext is authenticated as associated data.
+const PASSWORD_VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const PASSWORD_HEADER_LEN: usize = 2 + 4 * 3 + SALT_LEN + NONCE_LEN;
+const ENCRYPTED_KEY_LABEL: &str = "ZCLI ENCRYPTED KEY";
+// containers come from who knows where: refuse to allocate more than 4 GiB or to
+// spend more than a few minutes deriving the key, and never write one past that
+pub const MAX_M_COST: u32 = 4 * 1024 * 1024;
+pub const MAX_T_COST: u32 = 32;
+pub const MAX_P_COST: u32 = 16;
+
+/// Argon2id cost, memory in KiB. The default is the OWASP recommendation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordKdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for PasswordKdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+impl PasswordKdfParams {
+    /// The bounds decryption enforces, checked when encrypting too so every container
+    /// written can be read back.
+    pub fn validate(&self) -> Result<(), EncryptError> {
+        let bounds = [
+            ("memory cost", self.m_cost, MAX_M_COST),
+            ("iterations", self.t_cost, MAX_T_COST),
+            ("parallelism", self.p_cost, MAX_P_COST),
+        ];
+        for (name, value, max) in bounds {
+            if value > max {
+                return Err(EncryptError::InvalidKdfParams(format!(
+                    "{} {} is over {}",
+                    name, value, max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EncryptError {
+    #[error("ciphertext is too short, {0} bytes")]
+    TooShort(usize),
+    #[error("decryption failed: wrong key or the ciphertext was tampered with")]
+    Authentication,
+    #[error("unsupported container version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unsupported key derivation function {0}")]
+    UnsupportedKdf(u8),
+    #[error("invalid key derivation parameters: {0}")]
+    InvalidKdfParams(String),
+}
+
+/// Encrypt with XChaCha20-Poly1305 under a random nonce, returning `nonce || ciphertext || tag`.
+/// The key is read the way blake3 keys are, see `load_blake3_key`.
+pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>> {
+    let cipher = cipher(&load_blake3_key(key)?);
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+
+    // 192 bit nonces are safe to pick at random
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+
+    let mut ret = nonce.to_vec();
+    ret.extend(ciphertext);
+    Ok(ret)
+}
+
+pub fn process_text_decrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    let cipher = cipher(&load_blake3_key(key)?);
+    if data.len() < NONCE_LEN + TAG_LEN {
+        return Err(EncryptError::TooShort(data.len()).into());
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| EncryptError::Authentication)?;
+    Ok(plaintext)
+}
+
+/// Encrypt under a key derived from `password`, into a container that holds
+/// everything but the password needed to decrypt it.
+pub fn process_text_encrypt_password(
+    reader: &mut dyn Read,
+    password: &str,
+    params: PasswordKdfParams,
+) -> Result<Vec<u8>> {
+    params.validate()?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+
+    let mut salt = [0u8; SALT_LEN];
+    rand::RngCore::fill_bytes(&mut OsRng, &mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut container = vec![PASSWORD_VERSION, KDF_ARGON2ID];
+    for n in [params.m_cost, params.t_cost, params.p_cost] {
+        container.extend(n.to_be_bytes());
+    }
+    container.extend(salt);
+    container.extend(nonce);
+
+    let key = derive_key(password, &salt, params)?;
+    let ciphertext = cipher(&key)
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad: &container,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+    container.extend(ciphertext);
+    Ok(container)
+}
+
+pub fn process_text_decrypt_password(data: &[u8], password: &str) -> Result<Vec<u8>> {
+    if data.len() < PASSWORD_HEADER_LEN + TAG_LEN {
+        return Err(EncryptError::TooShort(data.len()).into());
+    }
+    let (header, ciphertext) = data.split_at(PASSWORD_HEADER_LEN);
+    if header[0] != PASSWORD_VERSION {
+        return Err(EncryptError::UnsupportedVersion(header[0]).into());
+    }
+    if header[1] != KDF_ARGON2ID {
+        return Err(EncryptError::UnsupportedKdf(header[1]).into());
+    }
+    let u32_at = |i: usize| u32::from_be_bytes(header[i..i + 4].try_into().expect("4 bytes"));
+    let params = PasswordKdfParams {
+        m_cost: u32_at(2),
+        t_cost: u32_at(6),
+        p_cost: u32_at(10),
+    };
+    params.validate()?;
+    let salt = &header[14..14 + SALT_LEN];
+    let nonce = &header[14 + SALT_LEN..];
+
+    let key = derive_key(password, salt, params)?;
+    let plaintext = cipher(&key)
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| EncryptError::Authentication)?;
+    Ok(plaintext)
+}
+
+/// Wrap a private key in the password container, armored so it can be told apart
+/// from a plain key file when loaded.
+pub fn process_key_encrypt(
+    key: &[u8],
+    passphrase: &str,
+    params: PasswordKdfParams,
+) -> Result<String> {
+    let sealed = process_text_encrypt_password(&mut &key[..], passphrase, params)?;
+    let encoded = STANDARD.encode(sealed);
+    let mut armored = format!("-----BEGIN {}-----\n", ENCRYPTED_KEY_LABEL);
+    for line in encoded.as_bytes().chunks(64) {
+        armored.push_str(std::str::from_utf8(line)?);
+        armored.push('\n');
+    }
+    armored.push_str(&format!("-----END {}-----\n", ENCRYPTED_KEY_LABEL));
+    Ok(armored)
+}
+
+pub fn process_key_decrypt(armored: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let armored = std::str::from_utf8(armored)?.trim();
+    let body = armored
+        .strip_prefix(&format!("-----BEGIN {}-----", ENCRYPTED_KEY_LABEL))
+        .and_then(|s| s.strip_suffix(&format!("-----END {}-----", ENCRYPTED_KEY_LABEL)))
+        .ok_or_else(|| anyhow::anyhow!("not an encrypted key"))?;
+    let body: String = body.split_whitespace().collect();
+    process_text_decrypt_password(&STANDARD.decode(body)?, passphrase)
+}
+
+pub fn is_encrypted_key(data: &[u8]) -> bool {
+    data.trim_ascii_start()
+        .starts_with(format!("-----BEGIN {}-----", ENCRYPTED_KEY_LABEL).as_bytes())
+}
+
+fn derive_key(password: &str, salt: &[u8], params: PasswordKdfParams) -> Result<[u8; KEY_LEN]> {
+    let p = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_LEN))
+        .map_err(|e| EncryptError::InvalidKdfParams(e.to_string()))?;
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, p)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("argon2 failed: {}", e))?;
+    Ok(key)
+}
+
+fn cipher(key: &[u8; KEY_LEN]) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(key.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"z8A#kWq2+pL0vN4e!Yt7cR5uJ1gS9hBm";
+
+    #[test]
+    fn test_process_text_encrypt_decrypt() -> Result<()> {
+        let sealed = process_text_encrypt(&mut "hello".as_bytes(), KEY)?;
+        assert_eq!(sealed.len(), NONCE_LEN + 5 + TAG_LEN);
+        assert_eq!(process_text_decrypt(&sealed, KEY)?, b"hello");
+
+        // fresh nonce every time
+        let again = process_text_encrypt(&mut "hello".as_bytes(), KEY)?;
+        assert_ne!(sealed, again);
+
+        for i in [0, NONCE_LEN, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 1;
+            let err = process_text_decrypt(&tampered, KEY).unwrap_err();
+            assert_eq!(
+                err.downcast::<EncryptError>()?,
+                EncryptError::Authentication
+            );
+        }
+
+        let mut other = KEY.to_vec();
+        other[0] ^= 1;
+        assert!(process_text_decrypt(&sealed, &other).is_err());
+        assert!(process_text_decrypt(&sealed[..20], KEY).is_err());
+        assert!(process_text_encrypt(&mut "hello".as_bytes(), b"short").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_encrypt_password() -> Result<()> {
+        // cheap parameters, the defaults take a while
+        let params = PasswordKdfParams {
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        let sealed = process_text_encrypt_password(&mut "config".as_bytes(), "hunter2", params)?;
+        assert_eq!(sealed[0], PASSWORD_VERSION);
+        assert_eq!(
+            process_text_decrypt_password(&sealed, "hunter2")?,
+            b"config"
+        );
+
+        let err = process_text_decrypt_password(&sealed, "hunter3").unwrap_err();
+        assert_eq!(
+            err.downcast::<EncryptError>()?,
+            EncryptError::Authentication
+        );
+
+        // the parameters are authenticated too
+        let mut tampered = sealed.clone();
+        tampered[5] ^= 1;
+        assert!(process_text_decrypt_password(&tampered, "hunter2").is_err());
+
+        // costs a crafted header could use to hang decryption are refused up front,
+        // and can't be written in the first place
+        for (offset, max) in [(2, MAX_M_COST), (6, MAX_T_COST), (10, MAX_P_COST)] {
+            let mut crafted = sealed.clone();
+            crafted[offset..offset + 4].copy_from_slice(&(max + 1).to_be_bytes());
+            let err = process_text_decrypt_password(&crafted, "hunter2").unwrap_err();
+            assert!(matches!(
+                err.downcast::<EncryptError>()?,
+                EncryptError::InvalidKdfParams(_)
+            ));
+        }
+        for params in [
+            PasswordKdfParams {
+                m_cost: MAX_M_COST + 1,
+                ..params
+            },
+            PasswordKdfParams {
+                t_cost: MAX_T_COST + 1,
+                ..params
+            },
+            PasswordKdfParams {
+                p_cost: MAX_P_COST + 1,
+                ..params
+            },
+        ] {
+            let err = process_text_encrypt_password(&mut "x".as_bytes(), "hunter2", params);
+            assert!(matches!(
+                err.unwrap_err().downcast::<EncryptError>()?,
+                EncryptError::InvalidKdfParams(_)
+            ));
+        }
+
+        let mut tampered = sealed.clone();
+        tampered[0] = 9;
+        let err = process_text_decrypt_password(&tampered, "hunter2").unwrap_err();
+        assert_eq!(
+            err.downcast::<EncryptError>()?,
+            EncryptError::UnsupportedVersion(9)
+        );
+
+        let armored = process_key_encrypt(KEY, "hunter2", params)?;
+        assert!(is_encrypted_key(armored.as_bytes()));
+        assert!(!is_encrypted_key(KEY));
+        assert_eq!(process_key_decrypt(armored.as_bytes(), "hunter2")?, KEY);
+        assert!(process_key_decrypt(armored.as_bytes(), "hunter3").is_err());
+        Ok(())
+    }
+}
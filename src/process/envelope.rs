@@ -0,0 +1,243 @@
+use crate::{text_signer, text_verifier, TextSignFormat};
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use thiserror::Error;
+
+const ENVELOPE_VERSION: u8 = 1;
+const COMPACT_PREFIX: &str = "zsig1";
+
+/// A signature that describes itself, so verifying needs only the key.
+///
+/// The metadata is signed along with the content: the signature covers
+/// `preamble() || content`, so the timestamp and comment can't be swapped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureEnvelope {
+    pub version: u8,
+    pub alg: String,
+    pub key_id: String,
+    /// Seconds since the epoch.
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// URL-safe base64, like bare signatures.
+    pub sig: String,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("malformed signature envelope: {0}")]
+    Malformed(String),
+    #[error("unsupported signature envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unknown signature algorithm {0}")]
+    UnknownAlg(String),
+    #[error("signature was made by {alg} key {expected}, but the given key is {found}")]
+    KeyMismatch {
+        alg: String,
+        expected: String,
+        found: String,
+    },
+    #[error("signature does not match the content")]
+    InvalidSignature,
+}
+
+pub fn process_text_sign_envelope(
+    reader: &mut dyn Read,
+    key: &[u8],
+    format: TextSignFormat,
+    timestamp: i64,
+    comment: Option<String>,
+) -> Result<SignatureEnvelope> {
+    anyhow::ensure!(
+        !matches!(format, TextSignFormat::SshSig),
+        "sshsig signatures are armored on their own and not put in an envelope"
+    );
+    let signer = text_signer(format, key)?;
+    let mut envelope = SignatureEnvelope {
+        version: ENVELOPE_VERSION,
+        alg: format.to_string(),
+        key_id: signer.key_id(),
+        timestamp,
+        comment,
+        sig: String::new(),
+    };
+    let preamble = envelope.preamble();
+    let sig = signer.sign(&mut preamble.as_slice().chain(reader))?;
+    envelope.sig = URL_SAFE_NO_PAD.encode(sig);
+    Ok(envelope)
+}
+
+/// Verify with the algorithm the envelope names. A key other than the signing one is
+/// reported as such rather than as a bad signature.
+pub fn process_text_verify_envelope(
+    reader: &mut dyn Read,
+    key: &[u8],
+    envelope: &SignatureEnvelope,
+) -> Result<()> {
+    if envelope.version != ENVELOPE_VERSION {
+        return Err(SignatureError::UnsupportedVersion(envelope.version).into());
+    }
+    let format = envelope.format()?;
+    let verifier = text_verifier(format, key)?;
+    let found = verifier.key_id();
+    if found != envelope.key_id {
+        return Err(SignatureError::KeyMismatch {
+            alg: envelope.alg.clone(),
+            expected: envelope.key_id.clone(),
+            found,
+        }
+        .into());
+    }
+
+    let sig = URL_SAFE_NO_PAD
+        .decode(&envelope.sig)
+        .map_err(|e| SignatureError::Malformed(e.to_string()))?;
+    let preamble = envelope.preamble();
+    if !verifier.verify(&mut preamble.as_slice().chain(reader), &sig)? {
+        return Err(SignatureError::InvalidSignature.into());
+    }
+    Ok(())
+}
+
+impl SignatureEnvelope {
+    pub fn format(&self) -> Result<TextSignFormat, SignatureError> {
+        self.alg
+            .parse()
+            .map_err(|_| SignatureError::UnknownAlg(self.alg.clone()))
+    }
+
+    /// `zsig1.<alg>.<key id>.<timestamp>.<base64 comment>.<sig>`, for command lines and headers.
+    pub fn to_compact(&self) -> String {
+        let comment = self.comment.as_deref().unwrap_or_default();
+        format!(
+            "{}.{}.{}.{}.{}.{}",
+            COMPACT_PREFIX,
+            self.alg,
+            self.key_id,
+            self.timestamp,
+            URL_SAFE_NO_PAD.encode(comment),
+            self.sig
+        )
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse either form, `None` if `s` is not an envelope at all (a bare signature).
+    pub fn parse(s: &str) -> Option<Result<Self, SignatureError>> {
+        let s = s.trim();
+        if s.starts_with('{') {
+            return Some(
+                serde_json::from_str(s).map_err(|e| SignatureError::Malformed(e.to_string())),
+            );
+        }
+        let rest = s.strip_prefix(COMPACT_PREFIX)?.strip_prefix('.')?;
+        Some(Self::from_compact(rest))
+    }
+
+    fn from_compact(rest: &str) -> Result<Self, SignatureError> {
+        let malformed = |what: &str| SignatureError::Malformed(what.to_string());
+        let parts: Vec<_> = rest.split('.').collect();
+        let [alg, key_id, timestamp, comment, sig] = parts[..] else {
+            return Err(malformed("expected 6 dot separated fields"));
+        };
+        let comment = URL_SAFE_NO_PAD
+            .decode(comment)
+            .ok()
+            .and_then(|c| String::from_utf8(c).ok())
+            .ok_or_else(|| malformed("invalid comment"))?;
+        Ok(Self {
+            version: ENVELOPE_VERSION,
+            alg: alg.to_string(),
+            key_id: key_id.to_string(),
+            timestamp: timestamp
+                .parse()
+                .map_err(|_| malformed("invalid timestamp"))?,
+            comment: (!comment.is_empty()).then_some(comment),
+            sig: sig.to_string(),
+        })
+    }
+
+    // length prefixed so that no two envelopes share a preamble
+    fn preamble(&self) -> Vec<u8> {
+        let comment = self.comment.as_deref().unwrap_or_default();
+        let mut preamble = format!("zcli signature v{}\n", self.version).into_bytes();
+        for field in [
+            self.alg.as_str(),
+            self.key_id.as_str(),
+            &self.timestamp.to_string(),
+            comment,
+        ] {
+            preamble.extend((field.len() as u64).to_le_bytes());
+            preamble.extend(field.as_bytes());
+        }
+        preamble
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_text_key_generate, KeyFormat};
+
+    const KEY: &[u8] = b"z8A#kWq2+pL0vN4e!Yt7cR5uJ1gS9hBm";
+
+    fn err(ret: Result<()>) -> SignatureError {
+        ret.unwrap_err().downcast().unwrap()
+    }
+
+    #[test]
+    fn test_envelope_round_trip() -> Result<()> {
+        let ed25519 = process_text_key_generate(TextSignFormat::Ed25519, KeyFormat::Raw)?;
+        let (sk, pk) = (&ed25519["ed25519.sk"], &ed25519["ed25519.pk"]);
+        let envelope = process_text_sign_envelope(
+            &mut "hello".as_bytes(),
+            sk,
+            TextSignFormat::Ed25519,
+            1_700_000_000,
+            Some("release v1.2".to_string()),
+        )?;
+        assert_eq!(envelope.key_id, crate::key_id(pk));
+
+        for form in [envelope.to_compact(), envelope.to_json()?] {
+            let parsed = SignatureEnvelope::parse(&form).unwrap()?;
+            assert_eq!(parsed, envelope);
+            process_text_verify_envelope(&mut "hello".as_bytes(), pk, &parsed)?;
+        }
+        assert!(SignatureEnvelope::parse("c2lnbmF0dXJl").is_none());
+
+        let ret = process_text_verify_envelope(&mut "hellO".as_bytes(), pk, &envelope);
+        assert_eq!(err(ret), SignatureError::InvalidSignature);
+
+        // the metadata is signed too
+        let mut altered = envelope.clone();
+        altered.timestamp += 1;
+        let ret = process_text_verify_envelope(&mut "hello".as_bytes(), pk, &altered);
+        assert_eq!(err(ret), SignatureError::InvalidSignature);
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_key_mismatch() -> Result<()> {
+        let envelope = process_text_sign_envelope(
+            &mut "hello".as_bytes(),
+            KEY,
+            TextSignFormat::Blake3,
+            0,
+            None,
+        )?;
+        let mut other = KEY.to_vec();
+        other[0] ^= 1;
+        let ret = process_text_verify_envelope(&mut "hello".as_bytes(), &other, &envelope);
+        assert!(matches!(err(ret), SignatureError::KeyMismatch { .. }));
+
+        let mut unknown = envelope.clone();
+        unknown.alg = "rsa".to_string();
+        let ret = process_text_verify_envelope(&mut "hello".as_bytes(), KEY, &unknown);
+        assert_eq!(err(ret), SignatureError::UnknownAlg("rsa".to_string()));
+        Ok(())
+    }
+}
@@ -1,9 +1,70 @@
+use crate::PwdKdf;
+use anyhow::Result;
+use argon2::{Algorithm, Argon2, Params, Version};
 use rand::seq::SliceRandom;
+use thiserror::Error;
 
 const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
 const NUMBER: &[u8] = b"1234567890";
 const SYMBOL: &[u8] = b"!@#$%^&*_";
+const VOWEL: &[u8] = b"aeiou";
+const CONSONANT: &[u8] = b"bcdfghjklmnpqrstvwxyz";
+
+pub const MAX_PWD_LENGTH: u8 = 128;
+pub const MIN_PIN_LENGTH: u8 = 4;
+
+// changing any of these changes every derived password
+const DERIVE_CONTEXT: &str = "zcli genpwd derive v1";
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const SCRYPT_LOG_N: u8 = 15;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GenPwdError {
+    #[error("length {length} is too short for {required} required character classes")]
+    LengthTooShort { length: u8, required: usize },
+    #[error("no character class is enabled, the alphabet is empty")]
+    EmptyAlphabet,
+    #[error("length {length} is over the limit of {max}")]
+    LengthOverLimit { length: u8, max: u8 },
+    #[error("pin length {length} is under the minimum of {min} digits")]
+    PinTooShort { length: u8, min: u8 },
+}
+
+pub struct DeriveParams<'a> {
+    pub site: &'a str,
+    pub login: &'a str,
+    pub counter: u32,
+    pub kdf: PwdKdf,
+}
+
+/// Check that a password of `length` can hold one char of every enabled class.
+pub fn validate_genpwd(
+    length: u8,
+    no_upper: bool,
+    lower: bool,
+    number: bool,
+    symbol: bool,
+) -> Result<(), GenPwdError> {
+    let required = [!no_upper, lower, number, symbol]
+        .iter()
+        .filter(|enabled| **enabled)
+        .count();
+    if required == 0 {
+        return Err(GenPwdError::EmptyAlphabet);
+    }
+    if length > MAX_PWD_LENGTH {
+        return Err(GenPwdError::LengthOverLimit {
+            length,
+            max: MAX_PWD_LENGTH,
+        });
+    }
+    if (length as usize) < required {
+        return Err(GenPwdError::LengthTooShort { length, required });
+    }
+    Ok(())
+}
 
 pub fn process_genpwd(
     length: u8,
@@ -12,6 +73,8 @@ pub fn process_genpwd(
     number: bool,
     symbol: bool,
 ) -> anyhow::Result<String> {
+    validate_genpwd(length, no_upper, lower, number, symbol)?;
+
     let mut rng = rand::thread_rng();
     let mut password = Vec::new();
     let mut chars = Vec::new();
@@ -36,12 +99,305 @@ pub fn process_genpwd(
         password.push(*SYMBOL.choose(&mut rng).expect("won't empty"));
     }
 
-    for _ in 0..(length - password.len() as u8) {
-        //let idx = rng.gen_range(0..chars.len());
-        let c = chars.choose(&mut rng).expect("won not empty");
+    for _ in password.len()..length as usize {
+        let c = chars.choose(&mut rng).expect("validated not empty");
         password.push(*c);
     }
     password.shuffle(&mut rng);
 
     Ok(String::from_utf8(password)?)
 }
+
+/// Alternating consonant/vowel syllables, e.g. `Bonatuvi`.
+pub fn process_genpwd_pronounceable(length: u8, no_upper: bool) -> Result<String> {
+    // only lower case letters are drawn, the capital is applied afterwards
+    validate_genpwd(length, true, true, false, false)?;
+    let mut rng = rand::thread_rng();
+    let mut password: Vec<u8> = (0..length as usize)
+        .map(|i| {
+            let class = if i % 2 == 0 { CONSONANT } else { VOWEL };
+            *class.choose(&mut rng).expect("won't empty")
+        })
+        .collect();
+    if !no_upper {
+        if let Some(c) = password.first_mut() {
+            c.make_ascii_uppercase();
+        }
+    }
+
+    Ok(String::from_utf8(password)?)
+}
+
+/// Numeric code that is not all the same digit, a sequence like 123456, 975310 or
+/// 246802, or a repetition like 121212.
+pub fn process_genpwd_pin(length: u8) -> Result<String> {
+    if length < MIN_PIN_LENGTH {
+        return Err(GenPwdError::PinTooShort {
+            length,
+            min: MIN_PIN_LENGTH,
+        }
+        .into());
+    }
+    let mut rng = rand::thread_rng();
+    loop {
+        let pin: Vec<u8> = (0..length)
+            .map(|_| *NUMBER.choose(&mut rng).expect("won't empty"))
+            .collect();
+        if !is_trivial_pin(&pin) {
+            return Ok(String::from_utf8(pin)?);
+        }
+    }
+}
+
+fn is_trivial_pin(pin: &[u8]) -> bool {
+    let step = (pin[1] + 10 - pin[0]) % 10;
+    let constant_step = pin.windows(2).all(|w| (w[1] + 10 - w[0]) % 10 == step);
+    let repeated = (1..=pin.len() / 2)
+        .filter(|unit| pin.len().is_multiple_of(*unit))
+        .any(|unit| pin.chunks(unit).all(|c| c == &pin[..unit]));
+    // step 0 is all-same, 1 and 9 are ascending/descending runs (with 9 -> 0 wrapping),
+    // 2 and 8 are the same runs skipping every other digit like 135791
+    (constant_step && matches!(step, 0 | 1 | 2 | 8 | 9)) || repeated
+}
+
+/// Fill a template where each letter stands for a character class:
+/// `C`/`c` consonant, `V`/`v` vowel, `A`/`a` letter (upper/lower case),
+/// `9` digit, `s` symbol, `x` any of them. `\` escapes the next char,
+/// everything else is copied as is.
+pub fn process_genpwd_pattern(pattern: &str) -> Result<String> {
+    let mut rng = rand::thread_rng();
+    let any: Vec<u8> = [UPPER, LOWER, NUMBER, SYMBOL].concat();
+    let mut password = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        let class: &[u8] = match c {
+            'C' | 'c' => CONSONANT,
+            'V' | 'v' => VOWEL,
+            'A' | 'a' => LOWER,
+            '9' => NUMBER,
+            's' => SYMBOL,
+            'x' => &any,
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("pattern ends with a dangling escape"))?;
+                let mut buf = [0u8; 4];
+                password.extend_from_slice(escaped.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            _ => {
+                let mut buf = [0u8; 4];
+                password.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+        };
+        let mut picked = *class.choose(&mut rng).expect("won't empty");
+        if c.is_ascii_uppercase() {
+            picked.make_ascii_uppercase();
+        }
+        password.push(picked);
+    }
+
+    Ok(String::from_utf8(password)?)
+}
+
+/// Derive a site password from a master password, the same inputs always give the
+/// same password so it never has to be stored.
+pub fn process_genpwd_derive(
+    master: &str,
+    params: &DeriveParams,
+    length: u8,
+    no_upper: bool,
+    lower: bool,
+    number: bool,
+    symbol: bool,
+) -> Result<String> {
+    validate_genpwd(length, no_upper, lower, number, symbol)?;
+
+    let seed = derive_seed(master, params)?;
+    let mut stream = blake3::Hasher::new_keyed(&seed).finalize_xof();
+    let mut password = Vec::new();
+    let mut chars = Vec::new();
+
+    let classes = [
+        (!no_upper, UPPER),
+        (lower, LOWER),
+        (number, NUMBER),
+        (symbol, SYMBOL),
+    ];
+    for (_, class) in classes.iter().filter(|(enabled, _)| *enabled) {
+        chars.extend_from_slice(class);
+        password.push(class[pick(&mut stream, class.len())]);
+    }
+
+    for _ in password.len()..length as usize {
+        password.push(chars[pick(&mut stream, chars.len())]);
+    }
+    // Fisher-Yates, so the guaranteed class chars don't always come first
+    for i in (1..password.len()).rev() {
+        let j = pick(&mut stream, i + 1);
+        password.swap(i, j);
+    }
+
+    Ok(String::from_utf8(password)?)
+}
+
+fn derive_seed(master: &str, params: &DeriveParams) -> Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new_derive_key(DERIVE_CONTEXT);
+    for field in [params.site.as_bytes(), params.login.as_bytes()] {
+        hasher.update(&(field.len() as u64).to_be_bytes());
+        hasher.update(field);
+    }
+    hasher.update(&params.counter.to_be_bytes());
+    let salt = hasher.finalize();
+
+    let mut seed = [0u8; 32];
+    match params.kdf {
+        PwdKdf::Argon2id => {
+            let p = Params::new(ARGON2_M_COST, ARGON2_T_COST, 1, Some(seed.len()))
+                .map_err(|e| anyhow::anyhow!("invalid argon2 params: {}", e))?;
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, p)
+                .hash_password_into(master.as_bytes(), salt.as_bytes(), &mut seed)
+                .map_err(|e| anyhow::anyhow!("argon2 failed: {}", e))?;
+        }
+        PwdKdf::Scrypt => {
+            let p = scrypt::Params::new(SCRYPT_LOG_N, 8, 1, seed.len())?;
+            scrypt::scrypt(master.as_bytes(), salt.as_bytes(), &p, &mut seed)?;
+        }
+    }
+
+    Ok(seed)
+}
+
+// uniform index in 0..n, rejecting values that would bias the modulo
+fn pick(stream: &mut blake3::OutputReader, n: usize) -> usize {
+    let n = n as u32;
+    let zone = u32::MAX - u32::MAX % n;
+    loop {
+        let mut buf = [0u8; 4];
+        stream.fill(&mut buf);
+        let v = u32::from_be_bytes(buf);
+        if v < zone {
+            return (v % n) as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn prop_process_genpwd(
+            length in any::<u8>(),
+            no_upper in any::<bool>(),
+            lower in any::<bool>(),
+            number in any::<bool>(),
+            symbol in any::<bool>(),
+        ) {
+            let classes = [(!no_upper, UPPER), (lower, LOWER), (number, NUMBER), (symbol, SYMBOL)];
+            let enabled: Vec<&[u8]> = classes.iter().filter(|(e, _)| *e).map(|(_, c)| *c).collect();
+
+            match process_genpwd(length, no_upper, lower, number, symbol) {
+                Ok(pwd) => {
+                    prop_assert_eq!(pwd.len(), length as usize);
+                    for class in &enabled {
+                        prop_assert!(pwd.bytes().any(|c| class.contains(&c)));
+                    }
+                    prop_assert!(pwd.bytes().all(|c| enabled.iter().any(|class| class.contains(&c))));
+                }
+                Err(e) => {
+                    let e = e.downcast::<GenPwdError>().expect("typed error");
+                    let expected = if enabled.is_empty() {
+                        GenPwdError::EmptyAlphabet
+                    } else if length > MAX_PWD_LENGTH {
+                        GenPwdError::LengthOverLimit { length, max: MAX_PWD_LENGTH }
+                    } else {
+                        GenPwdError::LengthTooShort { length, required: enabled.len() }
+                    };
+                    prop_assert_eq!(e, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_process_genpwd_pin() -> Result<()> {
+        for _ in 0..100 {
+            let pin = process_genpwd_pin(6)?;
+            assert_eq!(pin.len(), 6);
+            assert!(pin.bytes().all(|c| c.is_ascii_digit()));
+            assert!(!is_trivial_pin(pin.as_bytes()));
+        }
+        for trivial in [
+            "123456", "000000", "654321", "789012", "121212", "123123", "135791",
+        ] {
+            assert!(is_trivial_pin(trivial.as_bytes()), "{}", trivial);
+        }
+        assert!(!is_trivial_pin(b"582016"));
+        let e = process_genpwd_pin(3)
+            .unwrap_err()
+            .downcast::<GenPwdError>()?;
+        assert_eq!(e, GenPwdError::PinTooShort { length: 3, min: 4 });
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpwd_pattern() -> Result<()> {
+        let pwd = process_genpwd_pattern("Cvcc-9999\\9")?;
+        let b = pwd.as_bytes();
+        assert_eq!(pwd.len(), 10);
+        assert!(CONSONANT.contains(&b[0].to_ascii_lowercase()) && b[0].is_ascii_uppercase());
+        assert!(VOWEL.contains(&b[1]));
+        assert!(CONSONANT.contains(&b[2]) && CONSONANT.contains(&b[3]));
+        assert_eq!(b[4], b'-');
+        assert!(b[5..9].iter().all(u8::is_ascii_digit));
+        assert_eq!(&pwd[9..], "9");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpwd_pronounceable() -> Result<()> {
+        let pwd = process_genpwd_pronounceable(8, true)?;
+        for (i, c) in pwd.bytes().enumerate() {
+            let class = if i % 2 == 0 { CONSONANT } else { VOWEL };
+            assert!(class.contains(&c));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpwd_derive() -> Result<()> {
+        // pinned so a change to the kdf params or the char picking can't slip through
+        for (kdf, expected) in [
+            (PwdKdf::Argon2id, "^DZYOvUnSN$KH#5k"),
+            (PwdKdf::Scrypt, "3Mc&3__fqEtCT3G9"),
+        ] {
+            let params = DeriveParams {
+                site: "example.com",
+                login: "alice",
+                counter: 1,
+                kdf,
+            };
+            let pwd = process_genpwd_derive("master", &params, 16, false, true, true, true)?;
+            let again = process_genpwd_derive("master", &params, 16, false, true, true, true)?;
+            assert_eq!(pwd, again);
+            assert_eq!(pwd, expected);
+            assert_eq!(pwd.len(), 16);
+            for class in [UPPER, LOWER, NUMBER, SYMBOL] {
+                assert!(pwd.bytes().any(|c| class.contains(&c)));
+            }
+
+            let params = DeriveParams {
+                counter: 2,
+                ..params
+            };
+            let other = process_genpwd_derive("master", &params, 16, false, true, true, true)?;
+            assert_ne!(pwd, other);
+        }
+        Ok(())
+    }
+}
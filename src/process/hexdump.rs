@@ -0,0 +1,177 @@
+use super::b64::read_full;
+use anyhow::Result;
+use data_encoding::HEXLOWER_PERMISSIVE;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+/// Layout of a dump, the defaults match `xxd`.
+#[derive(Debug, Clone, Copy)]
+pub struct HexdumpLayout {
+    /// Bytes per line.
+    pub cols: usize,
+    /// Bytes per space separated group, 0 for no spaces.
+    pub group: usize,
+}
+
+impl Default for HexdumpLayout {
+    fn default() -> Self {
+        Self { cols: 16, group: 2 }
+    }
+}
+
+/// Dump `length` bytes (or everything) starting at `offset` as
+/// `<offset>: <hex groups>  <ascii>` lines.
+pub fn process_hexdump(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    layout: HexdumpLayout,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<()> {
+    anyhow::ensure!(layout.cols > 0, "cols must be at least 1");
+    // stdin can't seek, so the skipped bytes are read and dropped
+    let skipped = io::copy(&mut reader.take(offset), &mut io::sink())?;
+    anyhow::ensure!(
+        skipped == offset,
+        "offset {} is past the end of the input",
+        offset
+    );
+    let mut reader = reader.take(length.unwrap_or(u64::MAX));
+
+    // the hex column is as wide as a full line, so the ascii gutters line up
+    let width = hex_width(layout, layout.cols);
+    let mut writer = BufWriter::new(writer);
+    let mut buf = vec![0u8; layout.cols];
+    let mut pos = offset;
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let line = &buf[..n];
+
+        let mut hex = String::with_capacity(width);
+        for (i, b) in line.iter().enumerate() {
+            if layout.group > 0 && i > 0 && i % layout.group == 0 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x}", b));
+        }
+        let ascii: String = line
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7e => b as char,
+                _ => '.',
+            })
+            .collect();
+        writeln!(writer, "{:08x}: {:<width$}  {}", pos, hex, ascii)?;
+        pos += n as u64;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Turn a dump back into bytes. The hex column ends at the first double space, so
+/// any `--cols`/`--group` layout works, as do plain hex lines without offsets.
+/// Gaps between the offsets are filled with zeros, like `xxd -r`.
+pub fn process_hexdump_reverse(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let mut written = 0u64;
+    let mut first = true;
+    for (n, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+
+        let hex = match line.split_once(':') {
+            Some((offset, rest)) => {
+                let offset = u64::from_str_radix(offset.trim(), 16)
+                    .map_err(|_| anyhow::anyhow!("line {}: invalid offset {:?}", n + 1, offset))?;
+                // a dump taken at an offset starts there
+                if first {
+                    written = offset;
+                }
+                anyhow::ensure!(
+                    offset >= written,
+                    "line {}: offset {:#x} goes backwards",
+                    n + 1,
+                    offset
+                );
+                io::copy(&mut io::repeat(0).take(offset - written), &mut writer)?;
+                written = offset;
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                rest.split("  ").next().unwrap_or_default()
+            }
+            None => line,
+        };
+        first = false;
+
+        let hex: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        let bytes = HEXLOWER_PERMISSIVE
+            .decode(&hex)
+            .map_err(|e| anyhow::anyhow!("line {}: {}", n + 1, e))?;
+        writer.write_all(&bytes)?;
+        written += bytes.len() as u64;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn hex_width(layout: HexdumpLayout, n: usize) -> usize {
+    let spaces = match layout.group {
+        0 => 0,
+        group => (n - 1) / group,
+    };
+    n * 2 + spaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(input: &[u8], layout: HexdumpLayout, offset: u64, length: Option<u64>) -> String {
+        let mut out = Vec::new();
+        process_hexdump(&mut &input[..], &mut out, layout, offset, length).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_process_hexdump() {
+        let input = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0hello, world";
+        assert_eq!(
+            dump(input, HexdumpLayout::default(), 0, None),
+            "00000000: 7f45 4c46 0201 0100 0000 0000 0000 0000  .ELF............\n\
+             00000010: 6865 6c6c 6f2c 2077 6f72 6c64            hello, world\n"
+        );
+        let layout = HexdumpLayout { cols: 4, group: 0 };
+        assert_eq!(
+            dump(input, layout, 17, Some(6)),
+            "00000011: 656c6c6f  ello\n00000015: 2c20      , \n"
+        );
+    }
+
+    #[test]
+    fn test_hexdump_round_trip() -> Result<()> {
+        let input: Vec<u8> = (0..=255).chain(b"  \n: 00".iter().copied()).collect();
+        for (cols, group) in [(16, 2), (8, 1), (32, 4), (7, 0), (1, 3)] {
+            let layout = HexdumpLayout { cols, group };
+            let text = dump(&input, layout, 0, None);
+            let mut out = Vec::new();
+            process_hexdump_reverse(&mut text.as_bytes(), &mut out)?;
+            assert_eq!(out, input, "cols {} group {}", cols, group);
+        }
+
+        // plain hex and gaps between offsets
+        let mut out = Vec::new();
+        process_hexdump_reverse(
+            &mut "00000000: 6869\n00000004: 21  !\n".as_bytes(),
+            &mut out,
+        )?;
+        assert_eq!(out, b"hi\0\0!");
+        let mut out = Vec::new();
+        process_hexdump_reverse(&mut "de ad\nBEEF\n".as_bytes(), &mut out)?;
+        assert_eq!(out, b"\xde\xad\xbe\xef");
+        Ok(())
+    }
+}
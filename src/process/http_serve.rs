@@ -70,11 +70,7 @@ async fn file_handler(
 }
 
 fn dirlist(p: PathBuf) -> Result<Vec<String>, io::Error> {
-    let e_result = fs::read_dir(p);
-    let entries = match e_result {
-        Ok(entries) => entries,
-        Err(e) => return Err(e),
-    };
+    let entries = fs::read_dir(p)?;
 
     let mut file_list: Vec<String> = Vec::new();
     for entry in entries {
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+// a symlink's line holds the hash of its target path instead of a content hash
+const SYMLINK_PREFIX: &str = "symlink:";
+
+/// Differences between a manifest and the directory it describes, paths are
+/// relative to the directory and `/` separated.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ManifestReport {
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+/// Hash every file under `dir` with Blake3 into `<hex hash>  <path>` lines, sorted
+/// by path like `b3sum`'s output. Symlinks are listed as `symlink:<hash of target>`,
+/// any other special file is an error. Files in `exclude`, such as the manifest
+/// itself, are skipped.
+pub fn process_manifest_build(dir: &Path, exclude: &[PathBuf]) -> Result<String> {
+    let mut manifest = String::new();
+    for (path, hash) in hash_dir(dir, exclude)? {
+        manifest.push_str(&manifest_line(&hash, &path));
+    }
+    Ok(manifest)
+}
+
+pub fn process_manifest_check(
+    dir: &Path,
+    manifest: &str,
+    exclude: &[PathBuf],
+) -> Result<ManifestReport> {
+    let mut expected = BTreeMap::new();
+    for (n, line) in manifest.lines().enumerate() {
+        let invalid = || anyhow::anyhow!("manifest line {} is not `<hash>  <path>`", n + 1);
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (hash, path) = line.split_once("  ").ok_or_else(invalid)?;
+        let path = if escaped {
+            unescape_path(path).ok_or_else(invalid)?
+        } else {
+            path.to_string()
+        };
+        expected.insert(path, hash.to_string());
+    }
+
+    let mut report = ManifestReport::default();
+    let mut actual = hash_dir(dir, exclude)?;
+    for (path, hash) in expected {
+        match actual.remove(&path) {
+            None => report.missing.push(path),
+            Some(actual) if actual != hash => report.modified.push(path),
+            Some(_) => {}
+        }
+    }
+    report.extra = actual.into_keys().collect();
+    Ok(report)
+}
+
+// like sha256sum, a path holding `\\`, `\n` or `\r` is escaped and its line starts
+// with `\\`, so every entry stays on one line
+fn manifest_line(hash: &str, path: &str) -> String {
+    if !path.contains(['\\', '\n', '\r']) {
+        return format!("{}  {}\n", hash, path);
+    }
+    let escaped = path
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\\{}  {}\n", hash, escaped)
+}
+
+fn unescape_path(path: &str) -> Option<String> {
+    let mut out = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(out)
+}
+
+impl ManifestReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.modified.is_empty()
+    }
+}
+
+fn hash_dir(dir: &Path, exclude: &[PathBuf]) -> Result<BTreeMap<String, String>> {
+    let exclude = exclude
+        .iter()
+        .filter_map(|p| p.canonicalize().ok())
+        .collect::<Vec<_>>();
+    let mut hashes = BTreeMap::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        let file_type = entry.file_type();
+        if file_type.is_dir()
+            || file_type.is_file() && exclude.contains(&entry.path().canonicalize()?)
+        {
+            continue;
+        }
+
+        let rel = entry.path().strip_prefix(dir)?;
+        let rel = rel
+            .components()
+            .map(|c| {
+                c.as_os_str()
+                    .to_str()
+                    .ok_or_else(|| anyhow::anyhow!("{} is not valid UTF-8", rel.display()))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join("/");
+        let hash = if file_type.is_symlink() {
+            // links are not followed, so pointing one elsewhere shows up as a modification
+            let target = fs::read_link(entry.path())?;
+            let hash = blake3::hash(target.as_os_str().as_encoded_bytes());
+            format!("{}{}", SYMLINK_PREFIX, hash.to_hex())
+        } else if file_type.is_file() {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(File::open(entry.path())?)?;
+            hasher.finalize().to_hex().to_string()
+        } else {
+            anyhow::bail!("{} is not a regular file, directory or symlink", rel);
+        };
+        hashes.insert(rel, hash);
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_manifest_report() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("zcli-manifest-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub"))?;
+        fs::write(dir.join("a.txt"), "a")?;
+        fs::write(dir.join("sub/b.txt"), "b")?;
+        fs::write(dir.join("sub/c.txt"), "c")?;
+        let manifest_path = dir.join("MANIFEST");
+        fs::write(&manifest_path, "")?;
+        let exclude = [manifest_path];
+
+        let manifest = process_manifest_build(&dir, &exclude)?;
+        let paths: Vec<_> = manifest.lines().map(|l| &l[66..]).collect();
+        assert_eq!(paths, ["a.txt", "sub/b.txt", "sub/c.txt"]);
+        assert!(manifest.starts_with(&format!("{}  a.txt\n", blake3::hash(b"a").to_hex())));
+        assert!(process_manifest_check(&dir, &manifest, &exclude)?.is_ok());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("a.txt", dir.join("link"))?;
+            let linked = process_manifest_build(&dir, &exclude)?;
+            let target = blake3::hash(b"a.txt").to_hex();
+            assert!(linked.contains(&format!("symlink:{}  link\n", target)));
+            fs::remove_file(dir.join("link"))?;
+            std::os::unix::fs::symlink("sub/b.txt", dir.join("link"))?;
+            let report = process_manifest_check(&dir, &linked, &exclude)?;
+            assert_eq!(report.modified, ["link"]);
+            fs::remove_file(dir.join("link"))?;
+        }
+
+        #[cfg(unix)]
+        {
+            // each entry has to stay on its own line to verify
+            for name in ["new\nline", "two  spaces", "back\\slash"] {
+                fs::write(dir.join(name), "x")?;
+            }
+            let escaped = process_manifest_build(&dir, &exclude)?;
+            assert_eq!(escaped.lines().count(), 6);
+            assert!(escaped.contains(&format!("\\{}  new\\nline\n", blake3::hash(b"x").to_hex())));
+            assert!(process_manifest_check(&dir, &escaped, &exclude)?.is_ok());
+            for name in ["new\nline", "two  spaces", "back\\slash"] {
+                fs::remove_file(dir.join(name))?;
+            }
+        }
+
+        fs::write(dir.join("sub/b.txt"), "B")?;
+        fs::remove_file(dir.join("sub/c.txt"))?;
+        fs::write(dir.join("d.txt"), "d")?;
+        let report = process_manifest_check(&dir, &manifest, &exclude)?;
+        fs::remove_dir_all(&dir)?;
+        assert_eq!(
+            report,
+            ManifestReport {
+                missing: vec!["sub/c.txt".into()],
+                extra: vec!["d.txt".into()],
+                modified: vec!["sub/b.txt".into()],
+            }
+        );
+        Ok(())
+    }
+}
@@ -1,11 +1,60 @@
 mod b64;
+mod clipboard;
 mod csv_convert;
+mod data_uri;
+mod doc_path;
+mod encrypt;
+mod envelope;
 mod gen_pwd;
+mod hexdump;
 mod http_serve;
+mod jwt;
+mod key_format;
+mod manifest;
+mod pwd_strength;
+mod sshsig;
 mod text;
+mod url_codec;
 
-pub use b64::{process_decode, process_encode};
+pub use b64::{detect_format, process_decode, process_decode_auto, process_encode};
+pub use clipboard::{clipboard_backend, process_clip, ClipboardBackend, CommandBackend, Osc52};
 pub use csv_convert::process_csv;
-pub use gen_pwd::process_genpwd;
+pub use data_uri::{process_decode_data_uri, process_encode_data_uri, sniff_mime};
+pub use doc_path::{process_decode_doc, process_encode_doc, DocPath, PathSegment};
+pub use encrypt::{
+    is_encrypted_key, process_key_decrypt, process_key_encrypt, process_text_decrypt,
+    process_text_decrypt_password, process_text_encrypt, process_text_encrypt_password,
+    EncryptError, PasswordKdfParams, MAX_M_COST, MAX_T_COST,
+};
+pub use envelope::{
+    process_text_sign_envelope, process_text_verify_envelope, SignatureEnvelope, SignatureError,
+};
+pub use gen_pwd::{
+    process_genpwd, process_genpwd_derive, process_genpwd_pattern, process_genpwd_pin,
+    process_genpwd_pronounceable, validate_genpwd, DeriveParams, GenPwdError, MAX_PWD_LENGTH,
+    MIN_PIN_LENGTH,
+};
+pub use hexdump::{process_hexdump, process_hexdump_reverse, HexdumpLayout};
 pub use http_serve::process_http_serve;
-pub use text::{process_text_key_generate, process_text_sign, process_text_verify};
+pub use jwt::{
+    jwt_times, process_jwt_decode, process_jwt_sign, process_jwt_verify,
+    process_jwt_verify_signature, Jwt, JwtError, JwtTime, JwtValidation,
+};
+pub use key_format::{
+    encode_blake3_key, encode_ecdsa_keys, encode_ed25519_signing_key, encode_ed25519_verifying_key,
+    encode_hmac_key, load_blake3_key, load_ecdsa_signing_key, load_ecdsa_verifying_key,
+    load_ed25519_signing_key, load_ed25519_verifying_key, load_hmac_key, EcdsaCurve,
+    EcdsaSigningKey, EcdsaVerifyingKey, KeyError,
+};
+pub use manifest::{process_manifest_build, process_manifest_check, ManifestReport};
+pub use pwd_strength::{process_pwd_strength, CrackTime, MatchKind, PwdMatch, PwdStrength};
+pub use sshsig::{
+    process_text_sign_sshsig, process_text_verify_sshsig, SshSigError, SshSigVerifier, SshSigner,
+    SSHSIG_DEFAULT_NAMESPACE,
+};
+pub use text::{
+    key_id, process_text_key_generate, process_text_sign, process_text_verify, secret_key_name,
+    text_signer, text_verifier, Blake3, EcdsaSigner, EcdsaVerifier, Ed25519Signer, Ed25519Verifier,
+    Ed25519phSigner, Ed25519phVerifier, HmacKey, HmacSha256, HmacSha512, TextSigner, TextVerifier,
+};
+pub use url_codec::{process_url_decode, process_url_encode, process_url_parse, ParsedUrl};
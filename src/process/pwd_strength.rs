@@ -0,0 +1,884 @@
+use crate::display_time;
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const PASSWORDS: &str = include_str!("../../assets/passwords.txt");
+// a short list kept in the binary, rarer words fall through to bruteforce scoring
+const WORDS: &str = include_str!("../../assets/words.txt");
+
+// only the first MAX_LENGTH chars are analysed, the rest is counted as bruteforce
+const MAX_LENGTH: usize = 128;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10000.0;
+const MIN_YEAR_SPACE: i32 = 20;
+
+const KEYBOARD_ROWS: [&str; 4] = [
+    "`~1!2@3#4$5%6^7&8*9(0)-_=+",
+    "qQwWeErRtTyYuUiIoOpP[{]}\\|",
+    "aAsSdDfFgGhHjJkKlL;:'\"",
+    "zZxXcCvVbBnNmM,<.>/?",
+];
+
+const L33T_TABLE: [(char, &[char]); 10] = [
+    ('4', &['a']),
+    ('@', &['a']),
+    ('8', &['b']),
+    ('(', &['c']),
+    ('3', &['e']),
+    ('6', &['g']),
+    ('1', &['i', 'l']),
+    ('!', &['i']),
+    ('0', &['o']),
+    ('$', &['s']),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PwdStrength {
+    pub score: u8,
+    pub guesses: f64,
+    pub guesses_log10: f64,
+    pub crack_times: Vec<CrackTime>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    pub sequence: Vec<PwdMatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CrackTime {
+    pub scenario: &'static str,
+    pub guesses_per_second: f64,
+    pub seconds: f64,
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PwdMatch {
+    pub start: usize,
+    pub end: usize,
+    // never serialized, the output must not leak pieces of the password
+    #[serde(skip)]
+    pub token: String,
+    pub guesses: f64,
+    #[serde(flatten)]
+    pub kind: MatchKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "pattern", rename_all = "snake_case")]
+pub enum MatchKind {
+    Dictionary {
+        dictionary: &'static str,
+        rank: usize,
+        reversed: bool,
+        l33t: bool,
+    },
+    Spatial {
+        turns: usize,
+        shifted: usize,
+    },
+    Repeat {
+        base_len: usize,
+        count: usize,
+    },
+    Sequence {
+        ascending: bool,
+    },
+    Date {
+        year: i32,
+        separator: bool,
+    },
+    Bruteforce,
+}
+
+struct Dictionary {
+    name: &'static str,
+    ranks: HashMap<&'static str, usize>,
+}
+
+struct Keyboard {
+    // char -> (position, shifted)
+    keys: HashMap<char, ((i32, i32), bool)>,
+    starting_positions: f64,
+    average_degree: f64,
+}
+
+pub fn process_pwd_strength(password: &str) -> PwdStrength {
+    let chars: Vec<char> = password.chars().collect();
+    let analysed = &chars[..chars.len().min(MAX_LENGTH)];
+
+    let sequence = most_guessable_sequence(analysed);
+    let guesses = sequence.iter().fold(1.0, |acc, m| acc * m.guesses);
+    let guesses = most_guessable_total(&sequence, guesses).clamp(1.0, f64::MAX);
+    // the bruteforced tail is added in log space, 10^n overflows f64 past ~300 chars
+    let tail = chars.len().saturating_sub(MAX_LENGTH) as f64;
+    let guesses_log10 = guesses.log10() + tail * BRUTEFORCE_CARDINALITY.log10();
+    let guesses = (guesses * BRUTEFORCE_CARDINALITY.powf(tail)).min(f64::MAX);
+    let score = guesses_to_score(guesses);
+    let (warning, suggestions) = feedback(score, &sequence);
+
+    PwdStrength {
+        score,
+        guesses,
+        guesses_log10,
+        crack_times: crack_times(guesses),
+        warning,
+        suggestions,
+        sequence,
+    }
+}
+
+fn most_guessable_total(sequence: &[PwdMatch], product: f64) -> f64 {
+    if sequence.is_empty() {
+        return 1.0;
+    }
+    let l = sequence.len();
+    factorial(l) * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1)
+}
+
+// dynamic programming over all matches, picking the non-overlapping sequence that
+// covers the password with the fewest total guesses (as zxcvbn does)
+fn most_guessable_sequence(chars: &[char]) -> Vec<PwdMatch> {
+    let n = chars.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut by_end: Vec<Vec<PwdMatch>> = vec![Vec::new(); n];
+    for m in omnimatch(chars) {
+        by_end[m.end].push(m);
+    }
+
+    // optimal[k][l] = (last match, product of guesses, total guesses)
+    let mut optimal: Vec<BTreeMap<usize, (PwdMatch, f64, f64)>> = vec![BTreeMap::new(); n];
+    for k in 0..n {
+        for m in &by_end[k] {
+            if m.start > 0 {
+                let prev: Vec<(usize, f64)> = optimal[m.start - 1]
+                    .iter()
+                    .map(|(l, (_, pi, _))| (*l, *pi))
+                    .collect();
+                for (l, pi) in prev {
+                    update(&mut optimal[k], m.clone(), l + 1, pi);
+                }
+            } else {
+                update(&mut optimal[k], m.clone(), 1, 1.0);
+            }
+        }
+
+        update(&mut optimal[k], bruteforce_match(chars, 0, k), 1, 1.0);
+        for i in 1..=k {
+            let prev: Vec<(usize, f64)> = optimal[i - 1]
+                .iter()
+                .filter(|(_, (m, _, _))| !matches!(m.kind, MatchKind::Bruteforce))
+                .map(|(l, (_, pi, _))| (*l, *pi))
+                .collect();
+            for (l, pi) in prev {
+                update(&mut optimal[k], bruteforce_match(chars, i, k), l + 1, pi);
+            }
+        }
+    }
+
+    let Some((mut l, _)) = optimal[n - 1]
+        .iter()
+        .min_by(|a, b| a.1 .2.partial_cmp(&b.1 .2).unwrap_or(Ordering::Equal))
+        .map(|(l, v)| (*l, v.2))
+    else {
+        return Vec::new();
+    };
+
+    let mut sequence = Vec::with_capacity(l);
+    let mut k = n as isize - 1;
+    while k >= 0 && l > 0 {
+        let m = optimal[k as usize][&l].0.clone();
+        k = m.start as isize - 1;
+        l -= 1;
+        sequence.push(m);
+    }
+    sequence.reverse();
+    sequence
+}
+
+fn update(slot: &mut BTreeMap<usize, (PwdMatch, f64, f64)>, m: PwdMatch, l: usize, pi: f64) {
+    let pi = pi * m.guesses;
+    let g = factorial(l) * pi + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1);
+    // a shorter sequence that is at least as cheap always wins
+    if slot.range(..=l).any(|(_, (_, _, other))| *other <= g) {
+        return;
+    }
+    slot.insert(l, (m, pi, g));
+}
+
+fn omnimatch(chars: &[char]) -> Vec<PwdMatch> {
+    let mut matches = Vec::new();
+    matches.extend(dictionary_matches(chars));
+    matches.extend(reverse_dictionary_matches(chars));
+    matches.extend(l33t_matches(chars));
+    matches.extend(spatial_matches(chars));
+    matches.extend(repeat_matches(chars));
+    matches.extend(sequence_matches(chars));
+    matches.extend(date_matches(chars));
+    matches
+}
+
+fn dictionaries() -> &'static [Dictionary] {
+    static DICTIONARIES: OnceLock<Vec<Dictionary>> = OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        [("passwords", PASSWORDS), ("english", WORDS)]
+            .into_iter()
+            .map(|(name, list)| {
+                let mut ranks = HashMap::new();
+                for (i, word) in list.lines().map(str::trim).enumerate() {
+                    if !word.is_empty() {
+                        ranks.entry(word).or_insert(i + 1);
+                    }
+                }
+                Dictionary { name, ranks }
+            })
+            .collect()
+    })
+}
+
+fn dictionary_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let lower: Vec<char> = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    // to_lowercase may change the length for some scripts, skip those
+    if lower.len() != chars.len() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    for dict in dictionaries() {
+        for i in 0..chars.len() {
+            for j in (i + 2)..chars.len() {
+                let word: String = lower[i..=j].iter().collect();
+                if let Some(&rank) = dict.ranks.get(word.as_str()) {
+                    let token: String = chars[i..=j].iter().collect();
+                    let guesses = rank as f64 * uppercase_variations(&token);
+                    matches.push(PwdMatch {
+                        start: i,
+                        end: j,
+                        token,
+                        guesses,
+                        kind: MatchKind::Dictionary {
+                            dictionary: dict.name,
+                            rank,
+                            reversed: false,
+                            l33t: false,
+                        },
+                    });
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn reverse_dictionary_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let reversed: Vec<char> = chars.iter().rev().copied().collect();
+    let n = chars.len();
+    dictionary_matches(&reversed)
+        .into_iter()
+        .filter_map(|mut m| {
+            let token: String = m.token.chars().rev().collect();
+            // palindromes are already found by the forward matcher
+            if token == m.token {
+                return None;
+            }
+            let (start, end) = (n - 1 - m.end, n - 1 - m.start);
+            m.start = start;
+            m.end = end;
+            m.token = token;
+            m.guesses *= 2.0;
+            if let MatchKind::Dictionary { reversed, .. } = &mut m.kind {
+                *reversed = true;
+            }
+            Some(m)
+        })
+        .collect()
+}
+
+fn l33t_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let subs: Vec<(char, &[char])> = L33T_TABLE
+        .iter()
+        .filter(|(c, _)| chars.contains(c))
+        .copied()
+        .collect();
+    if subs.is_empty() {
+        return Vec::new();
+    }
+
+    // enumerate every way to undo the substitutions, at most 2^n for ambiguous chars
+    let mut tables: Vec<HashMap<char, char>> = vec![HashMap::new()];
+    for (c, letters) in &subs {
+        tables = tables
+            .into_iter()
+            .flat_map(|t| {
+                letters.iter().map(move |l| {
+                    let mut t = t.clone();
+                    t.insert(*c, *l);
+                    t
+                })
+            })
+            .collect();
+    }
+
+    let mut matches = Vec::new();
+    for table in tables {
+        let translated: Vec<char> = chars
+            .iter()
+            .map(|c| table.get(c).copied().unwrap_or(*c))
+            .collect();
+        for mut m in dictionary_matches(&translated) {
+            let token: String = chars[m.start..=m.end].iter().collect();
+            let subbed: Vec<(char, char)> = table
+                .iter()
+                .filter(|(c, _)| token.contains(**c))
+                .map(|(c, l)| (*c, *l))
+                .collect();
+            if subbed.is_empty() || token.chars().count() < 4 {
+                continue;
+            }
+            m.guesses *= l33t_variations(&token, &subbed);
+            m.token = token;
+            if let MatchKind::Dictionary { l33t, .. } = &mut m.kind {
+                *l33t = true;
+            }
+            matches.push(m);
+        }
+    }
+    matches
+}
+
+fn keyboard() -> &'static Keyboard {
+    static KEYBOARD: OnceLock<Keyboard> = OnceLock::new();
+    KEYBOARD.get_or_init(|| {
+        let mut keys = HashMap::new();
+        let mut positions = HashMap::new();
+        for (y, row) in KEYBOARD_ROWS.iter().enumerate() {
+            let row: Vec<char> = row.chars().collect();
+            // rows below the digits are slanted half a key to the right
+            let offset = if y == 0 { 0 } else { 1 };
+            for (x, pair) in row.chunks(2).enumerate() {
+                let pos = (x as i32 + offset, y as i32);
+                keys.insert(pair[0], (pos, false));
+                keys.insert(pair[1], (pos, true));
+                positions.insert(pos, pair[0]);
+            }
+        }
+        let degrees: Vec<usize> = positions
+            .keys()
+            .map(|p| {
+                neighbours(*p)
+                    .iter()
+                    .filter(|n| positions.contains_key(n))
+                    .count()
+            })
+            .collect();
+        let average_degree = degrees.iter().sum::<usize>() as f64 / degrees.len() as f64;
+        Keyboard {
+            keys,
+            starting_positions: positions.len() as f64,
+            average_degree,
+        }
+    })
+}
+
+fn neighbours((x, y): (i32, i32)) -> [(i32, i32); 6] {
+    [
+        (x - 1, y),
+        (x, y - 1),
+        (x + 1, y - 1),
+        (x + 1, y),
+        (x, y + 1),
+        (x - 1, y + 1),
+    ]
+}
+
+fn spatial_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let kb = keyboard();
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + 2 < chars.len() {
+        let mut j = i;
+        let mut turns = 0;
+        let mut last_direction = None;
+        let mut shifted = usize::from(kb.keys.get(&chars[i]).is_some_and(|(_, s)| *s));
+        while j + 1 < chars.len() {
+            let (Some((cur, _)), Some((next, next_shifted))) =
+                (kb.keys.get(&chars[j]), kb.keys.get(&chars[j + 1]))
+            else {
+                break;
+            };
+            let Some(direction) = neighbours(*cur).iter().position(|n| n == next) else {
+                break;
+            };
+            if last_direction != Some(direction) {
+                turns += 1;
+                last_direction = Some(direction);
+            }
+            if *next_shifted {
+                shifted += 1;
+            }
+            j += 1;
+        }
+
+        if j - i + 1 >= 3 {
+            let token: String = chars[i..=j].iter().collect();
+            matches.push(PwdMatch {
+                start: i,
+                end: j,
+                guesses: spatial_guesses(j - i + 1, turns, shifted),
+                token,
+                kind: MatchKind::Spatial { turns, shifted },
+            });
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+fn spatial_guesses(len: usize, turns: usize, shifted: usize) -> f64 {
+    let kb = keyboard();
+    let mut guesses = 0.0;
+    for i in 2..=len {
+        for j in 1..=turns.min(i - 1) {
+            guesses +=
+                n_choose_k(i - 1, j - 1) * kb.starting_positions * kb.average_degree.powi(j as i32);
+        }
+    }
+    if shifted > 0 {
+        let unshifted = len - shifted;
+        if unshifted == 0 {
+            guesses *= 2.0;
+        } else {
+            guesses *= (1..=shifted.min(unshifted))
+                .map(|i| n_choose_k(shifted + unshifted, i))
+                .sum::<f64>();
+        }
+    }
+    guesses
+}
+
+fn repeat_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut best: Option<(usize, usize)> = None;
+        for unit in 1..=(chars.len() - i) / 2 {
+            let base = &chars[i..i + unit];
+            let mut count = 1;
+            while chars[i + count * unit..]
+                .get(..unit)
+                .is_some_and(|next| next == base)
+            {
+                count += 1;
+            }
+            let total = unit * count;
+            if count >= 2 && total >= 3 && best.is_none_or(|(u, c)| total > u * c) {
+                best = Some((unit, count));
+            }
+        }
+
+        match best {
+            Some((unit, count)) => {
+                let end = i + unit * count - 1;
+                let base = &chars[i..i + unit];
+                let base_guesses = if unit == 1 {
+                    BRUTEFORCE_CARDINALITY + 1.0
+                } else {
+                    process_pwd_strength(&base.iter().collect::<String>()).guesses
+                };
+                matches.push(PwdMatch {
+                    start: i,
+                    end,
+                    token: chars[i..=end].iter().collect(),
+                    guesses: base_guesses * count as f64,
+                    kind: MatchKind::Repeat {
+                        base_len: unit,
+                        count,
+                    },
+                });
+                i = end + 1;
+            }
+            None => i += 1,
+        }
+    }
+    matches
+}
+
+fn sequence_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + 2 < chars.len() {
+        let delta = chars[i + 1] as i64 - chars[i] as i64;
+        if delta == 0 || delta.abs() > 5 {
+            i += 1;
+            continue;
+        }
+        let mut j = i + 1;
+        while j + 1 < chars.len() && chars[j + 1] as i64 - chars[j] as i64 == delta {
+            j += 1;
+        }
+        if j - i + 1 >= 3 {
+            let first = chars[i];
+            let mut base = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            matches.push(PwdMatch {
+                start: i,
+                end: j,
+                token: chars[i..=j].iter().collect(),
+                guesses: base * (j - i + 1) as f64,
+                kind: MatchKind::Sequence {
+                    ascending: delta > 0,
+                },
+            });
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+fn date_matches(chars: &[char]) -> Vec<PwdMatch> {
+    let mut matches = Vec::new();
+    for i in 0..chars.len() {
+        for j in (i + 3)..chars.len().min(i + 10) {
+            let token: String = chars[i..=j].iter().collect();
+            if let Some((year, separator)) = parse_date(&token) {
+                let year_space = (year - reference_year()).abs().max(MIN_YEAR_SPACE) as f64;
+                let mut guesses = if token.len() == 4 {
+                    year_space
+                } else {
+                    year_space * 365.0
+                };
+                if separator {
+                    guesses *= 4.0;
+                }
+                matches.push(PwdMatch {
+                    start: i,
+                    end: j,
+                    token,
+                    guesses,
+                    kind: MatchKind::Date { year, separator },
+                });
+            }
+        }
+    }
+    matches
+}
+
+fn parse_date(token: &str) -> Option<(i32, bool)> {
+    if token.chars().all(|c| c.is_ascii_digit()) {
+        return match token.len() {
+            4 => token
+                .parse()
+                .ok()
+                .filter(|y| (1900..=2049).contains(y))
+                .map(|y| (y, false)),
+            6 | 8 => {
+                let n = token.len();
+                // d-m-y, m-d-y, y-m-d
+                let candidates = [
+                    (&token[..2], &token[2..4], &token[4..]),
+                    (&token[2..4], &token[..2], &token[4..]),
+                    (&token[n - 2..], &token[n - 4..n - 2], &token[..n - 4]),
+                ];
+                candidates
+                    .into_iter()
+                    .find_map(|(d, m, y)| valid_date(d, m, y))
+                    .map(|y| (y, false))
+            }
+            _ => None,
+        };
+    }
+
+    let sep = token.chars().find(|c| !c.is_ascii_digit())?;
+    if !" -/._\\".contains(sep) {
+        return None;
+    }
+    let parts: Vec<&str> = token.split(sep).collect();
+    if parts.len() != 3
+        || parts
+            .iter()
+            .any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    [
+        (parts[0], parts[1], parts[2]),
+        (parts[1], parts[0], parts[2]),
+        (parts[2], parts[1], parts[0]),
+    ]
+    .into_iter()
+    .find_map(|(d, m, y)| valid_date(d, m, y))
+    .map(|y| (y, true))
+}
+
+fn valid_date(day: &str, month: &str, year: &str) -> Option<i32> {
+    let (day, month): (u32, u32) = (day.parse().ok()?, month.parse().ok()?);
+    if !(1..=31).contains(&day) || !(1..=12).contains(&month) {
+        return None;
+    }
+    let year: i32 = match year.len() {
+        2 => {
+            let y: i32 = year.parse().ok()?;
+            if y > 50 {
+                1900 + y
+            } else {
+                2000 + y
+            }
+        }
+        4 => year.parse().ok().filter(|y| (1900..=2049).contains(y))?,
+        _ => return None,
+    };
+    Some(year)
+}
+
+fn bruteforce_match(chars: &[char], start: usize, end: usize) -> PwdMatch {
+    let len = end - start + 1;
+    let mut guesses = BRUTEFORCE_CARDINALITY.powi(len as i32);
+    // a single bruteforce char is never cheaper than a one-char guess
+    guesses = guesses.max(if len == 1 { 11.0 } else { 51.0 });
+    PwdMatch {
+        start,
+        end,
+        token: chars[start..=end].iter().collect(),
+        guesses,
+        kind: MatchKind::Bruteforce,
+    }
+}
+
+fn uppercase_variations(token: &str) -> f64 {
+    let upper = token.chars().filter(|c| c.is_uppercase()).count();
+    let lower = token.chars().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_upper = token.chars().next().is_some_and(char::is_uppercase);
+    let last_upper = token.chars().last().is_some_and(char::is_uppercase);
+    if lower == 0 || (upper == 1 && (first_upper || last_upper)) {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|i| n_choose_k(upper + lower, i))
+        .sum()
+}
+
+fn l33t_variations(token: &str, subbed: &[(char, char)]) -> f64 {
+    let lower = token.to_lowercase();
+    let mut variations = 1.0;
+    for (sub, letter) in subbed {
+        let s = lower.chars().filter(|c| c == sub).count();
+        let u = lower.chars().filter(|c| c == letter).count();
+        if s == 0 || u == 0 {
+            variations *= 2.0;
+        } else {
+            variations *= (1..=s.min(u)).map(|i| n_choose_k(s + u, i)).sum::<f64>();
+        }
+    }
+    variations
+}
+
+fn guesses_to_score(guesses: f64) -> u8 {
+    const DELTA: f64 = 5.0;
+    match guesses {
+        g if g < 1e3 + DELTA => 0,
+        g if g < 1e6 + DELTA => 1,
+        g if g < 1e8 + DELTA => 2,
+        g if g < 1e10 + DELTA => 3,
+        _ => 4,
+    }
+}
+
+fn crack_times(guesses: f64) -> Vec<CrackTime> {
+    [
+        ("online attack, throttled (100/hour)", 100.0 / 3600.0),
+        ("online attack, unthrottled (10/second)", 10.0),
+        ("offline attack, slow hash (1e4/second)", 1e4),
+        ("offline attack, fast hash (1e10/second)", 1e10),
+    ]
+    .into_iter()
+    .map(|(scenario, guesses_per_second)| {
+        let seconds = (guesses / guesses_per_second).min(f64::MAX);
+        CrackTime {
+            scenario,
+            guesses_per_second,
+            seconds,
+            display: display_time(seconds),
+        }
+    })
+    .collect()
+}
+
+fn feedback(score: u8, sequence: &[PwdMatch]) -> (Option<String>, Vec<String>) {
+    if sequence.is_empty() {
+        return (
+            None,
+            vec![
+                "Use a few words, avoid common phrases".to_string(),
+                "No need for symbols, digits, or uppercase letters".to_string(),
+            ],
+        );
+    }
+    if score > 2 {
+        return (None, Vec::new());
+    }
+
+    let longest = sequence
+        .iter()
+        .max_by_key(|m| m.end - m.start)
+        .expect("sequence is not empty");
+    let mut suggestions = vec!["Add another word or two. Uncommon words are better.".to_string()];
+    let warning = match &longest.kind {
+        MatchKind::Dictionary {
+            dictionary,
+            rank,
+            reversed,
+            l33t,
+        } => {
+            let warning = match (*dictionary, sequence.len() == 1) {
+                ("passwords", true) if *rank <= 10 => Some("This is a top-10 common password"),
+                ("passwords", true) if *rank <= 100 => Some("This is a top-100 common password"),
+                ("passwords", true) => Some("This is a very common password"),
+                ("passwords", false) => Some("This is similar to a commonly used password"),
+                (_, true) => Some("A word by itself is easy to guess"),
+                _ => None,
+            };
+            let token = &longest.token;
+            if token.chars().next().is_some_and(char::is_uppercase) {
+                suggestions.push("Capitalization doesn't help very much".to_string());
+            } else if token.chars().any(char::is_alphabetic) && token.to_uppercase() == *token {
+                suggestions
+                    .push("All-uppercase is almost as easy to guess as all-lowercase".to_string());
+            }
+            if *reversed {
+                suggestions.push("Reversed words aren't much harder to guess".to_string());
+            }
+            if *l33t {
+                suggestions.push(
+                    "Predictable substitutions like '@' instead of 'a' don't help very much"
+                        .to_string(),
+                );
+            }
+            warning
+        }
+        MatchKind::Spatial { turns, .. } => {
+            suggestions.push("Use a longer keyboard pattern with more turns".to_string());
+            if *turns == 1 {
+                Some("Straight rows of keys are easy to guess")
+            } else {
+                Some("Short keyboard patterns are easy to guess")
+            }
+        }
+        MatchKind::Repeat { base_len, .. } => {
+            suggestions.push("Avoid repeated words and characters".to_string());
+            if *base_len == 1 {
+                Some("Repeats like \"aaa\" are easy to guess")
+            } else {
+                Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"")
+            }
+        }
+        MatchKind::Sequence { .. } => {
+            suggestions.push("Avoid sequences".to_string());
+            Some("Sequences like abc or 6543 are easy to guess")
+        }
+        MatchKind::Date { .. } => {
+            suggestions.push("Avoid dates and years that are associated with you".to_string());
+            Some("Dates are often easy to guess")
+        }
+        MatchKind::Bruteforce => None,
+    };
+
+    (warning.map(str::to_string), suggestions)
+}
+
+fn reference_year() -> i32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    1970 + (secs / 31_556_952) as i32
+}
+
+fn factorial(n: usize) -> f64 {
+    (2..=n).map(|i| i as f64).product()
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_common_password_is_weak() {
+        let ret = process_pwd_strength("password");
+        assert_eq!(ret.score, 0);
+        assert_eq!(
+            ret.warning.as_deref(),
+            Some("This is a top-10 common password")
+        );
+    }
+
+    #[test]
+    fn test_patterns_are_detected() {
+        let kinds = |pwd: &str| {
+            process_pwd_strength(pwd)
+                .sequence
+                .into_iter()
+                .map(|m| m.kind)
+                .collect::<Vec<_>>()
+        };
+        assert!(matches!(
+            kinds("zxcvbnm")[0],
+            MatchKind::Spatial { .. } | MatchKind::Dictionary { .. }
+        ));
+        assert!(matches!(kinds("qazxswedc")[0], MatchKind::Spatial { .. }));
+        assert!(matches!(
+            kinds("p@ssw0rd")[0],
+            MatchKind::Dictionary { l33t: true, .. }
+        ));
+        assert!(matches!(kinds("aaaaaaaa")[0], MatchKind::Repeat { .. }));
+        assert!(matches!(kinds("13579")[0], MatchKind::Sequence { .. }));
+        assert!(matches!(
+            kinds("13/04/1987")[0],
+            MatchKind::Date { year: 1987, .. }
+        ));
+    }
+
+    #[test]
+    fn test_generated_password_is_strong() -> anyhow::Result<()> {
+        let pwd = crate::process_genpwd(16, false, true, true, true)?;
+        let ret = process_pwd_strength(&pwd);
+        assert_eq!(ret.score, 4);
+        assert!(ret.warning.is_none());
+
+        // long enough that 10^(len - MAX_LENGTH) alone overflows f64
+        let pwd = crate::process_genpwd(128, false, true, true, true)?.repeat(4);
+        let ret = process_pwd_strength(&pwd);
+        assert!(ret.guesses.is_finite());
+        assert!(ret.guesses_log10.is_finite() && ret.guesses_log10 > 384.0);
+        assert!(ret.crack_times.iter().all(|t| t.seconds.is_finite()));
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+use super::text::for_each_chunk;
+use crate::{
+    encode_ed25519_signing_key, encode_ed25519_verifying_key, key_id, load_ed25519_signing_key,
+    secret_key_name, KeyFormat, TextSignFormat, TextSigner, TextVerifier,
+};
+use anyhow::Result;
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use ed25519_dalek::{Signature as Ed25519Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256, Sha512};
+use ssh_key::{
+    public::{Ed25519PublicKey, KeyData},
+    Algorithm, HashAlg, LineEnding, PublicKey, Signature, SshSig,
+};
+use std::{collections::HashMap, io::Read};
+use thiserror::Error;
+
+/// What `ssh-keygen -Y sign` calls file signatures, git uses `git`.
+pub const SSHSIG_DEFAULT_NAMESPACE: &str = "file";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SshSigError {
+    #[error("malformed SSH signature: {0}")]
+    Malformed(String),
+    #[error("signature is for namespace {found}, expected {expected}")]
+    NamespaceMismatch { expected: String, found: String },
+    #[error("{0} SSH signatures are not supported, only ssh-ed25519")]
+    UnsupportedKey(String),
+    #[error("allowed signers line {line}: {reason}")]
+    AllowedSigners { line: usize, reason: String },
+    #[error("signing key {0} is not an allowed signer for this namespace and identity")]
+    NotAllowed(String),
+    #[error("signature does not match the content")]
+    InvalidSignature,
+}
+
+/// Signs as `ssh-keygen -Y sign` does, the output is an armored SSHSIG blob.
+pub struct SshSigner {
+    key: SigningKey,
+    namespace: String,
+}
+
+/// Checks SSHSIG signatures against the keys of an OpenSSH `allowed_signers` file.
+pub struct SshSigVerifier {
+    signers: Vec<AllowedSigner>,
+    namespace: String,
+    identity: Option<String>,
+}
+
+#[derive(Debug)]
+struct AllowedSigner {
+    principals: String,
+    namespaces: Option<Vec<String>>,
+    valid_after: Option<i64>,
+    valid_before: Option<i64>,
+    key: KeyData,
+}
+
+impl SshSigner {
+    pub fn try_new(key: impl AsRef<[u8]>, namespace: impl Into<String>) -> Result<Self> {
+        let namespace = namespace.into();
+        anyhow::ensure!(!namespace.is_empty(), "the namespace can't be empty");
+        Ok(Self {
+            key: load_ed25519_signing_key(key.as_ref())?,
+            namespace,
+        })
+    }
+
+    /// A key pair named like the ones ssh-keygen writes.
+    pub fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
+        let key = SigningKey::generate(&mut OsRng);
+        let mut map = HashMap::new();
+        map.insert(
+            secret_key_name(TextSignFormat::SshSig),
+            encode_ed25519_signing_key(&key, KeyFormat::OpenSsh)?,
+        );
+        map.insert(
+            "id_ed25519.pub",
+            encode_ed25519_verifying_key(&key.verifying_key(), KeyFormat::OpenSsh)?,
+        );
+        Ok(map)
+    }
+}
+
+impl TextSigner for SshSigner {
+    fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let signed_data = signed_data(reader, &self.namespace, HashAlg::Sha512)?;
+        let signature = self.key.sign(&signed_data).to_bytes();
+        let public_key = Ed25519PublicKey(self.key.verifying_key().to_bytes());
+        let sig = SshSig::new(
+            KeyData::Ed25519(public_key),
+            self.namespace.as_str(),
+            HashAlg::Sha512,
+            Signature::new(Algorithm::Ed25519, signature)?,
+        )?;
+        Ok(sig.to_pem(LineEnding::LF)?.into_bytes())
+    }
+
+    fn key_id(&self) -> String {
+        key_id(self.key.verifying_key().as_bytes())
+    }
+}
+
+impl SshSigVerifier {
+    /// `identity` is matched against the principals of the signing key, as with
+    /// `ssh-keygen -Y verify -I`. Without it any principal of the key will do.
+    pub fn try_new(
+        allowed_signers: &[u8],
+        namespace: impl Into<String>,
+        identity: Option<String>,
+    ) -> Result<Self> {
+        let allowed_signers = std::str::from_utf8(allowed_signers)
+            .map_err(|_| anyhow::anyhow!("allowed signers file is not UTF-8"))?;
+        Ok(Self {
+            signers: parse_allowed_signers(allowed_signers)?,
+            namespace: namespace.into(),
+            identity,
+        })
+    }
+
+    /// Verify `sig` and return the principals of the key that made it.
+    pub fn verify_signer(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<String, SshSigError> {
+        let sig = SshSig::from_pem(sig).map_err(|e| SshSigError::Malformed(e.to_string()))?;
+        if sig.namespace() != self.namespace {
+            return Err(SshSigError::NamespaceMismatch {
+                expected: self.namespace.clone(),
+                found: sig.namespace().to_string(),
+            });
+        }
+        let KeyData::Ed25519(public_key) = sig.public_key() else {
+            return Err(SshSigError::UnsupportedKey(sig.algorithm().to_string()));
+        };
+
+        let now = Utc::now().timestamp();
+        let signer = self
+            .signers
+            .iter()
+            .find(|signer| signer.key == *sig.public_key() && self.allows(signer, now))
+            .ok_or_else(|| SshSigError::NotAllowed(fingerprint(sig.public_key())))?;
+
+        let key = VerifyingKey::from_bytes(&public_key.0)
+            .map_err(|e| SshSigError::Malformed(e.to_string()))?;
+        let signature = <[u8; 64]>::try_from(sig.signature_bytes())
+            .map_err(|_| SshSigError::Malformed("ed25519 signatures are 64 bytes".into()))?;
+        let signed_data = signed_data(reader, sig.namespace(), sig.hash_alg())
+            .map_err(|e| SshSigError::Malformed(e.to_string()))?;
+        key.verify(&signed_data, &Ed25519Signature::from_bytes(&signature))
+            .map_err(|_| SshSigError::InvalidSignature)?;
+        Ok(signer.principals.clone())
+    }
+
+    fn allows(&self, signer: &AllowedSigner, now: i64) -> bool {
+        signer
+            .namespaces
+            .as_ref()
+            .is_none_or(|namespaces| match_pattern_list(namespaces, &self.namespace))
+            && signer.valid_after.is_none_or(|t| now >= t)
+            && signer.valid_before.is_none_or(|t| now <= t)
+            && self.identity.as_ref().is_none_or(|identity| {
+                let principals: Vec<_> = signer.principals.split(',').map(String::from).collect();
+                match_pattern_list(&principals, identity)
+            })
+    }
+}
+
+impl TextVerifier for SshSigVerifier {
+    fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        match self.verify_signer(reader, sig) {
+            Ok(_) => Ok(true),
+            Err(SshSigError::InvalidSignature | SshSigError::NotAllowed(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn key_id(&self) -> String {
+        let ids: Vec<_> = self
+            .signers
+            .iter()
+            .filter_map(|signer| match &signer.key {
+                KeyData::Ed25519(key) => Some(key_id(&key.0)),
+                _ => None,
+            })
+            .collect();
+        ids.join(",")
+    }
+}
+
+pub fn process_text_sign_sshsig(
+    reader: &mut dyn Read,
+    key: &[u8],
+    namespace: &str,
+) -> Result<String> {
+    let armored = SshSigner::try_new(key, namespace)?.sign(reader)?;
+    Ok(String::from_utf8(armored)?)
+}
+
+/// Returns the principals the signing key is allowed for.
+pub fn process_text_verify_sshsig(
+    reader: &mut dyn Read,
+    allowed_signers: &[u8],
+    sig: &[u8],
+    namespace: &str,
+    identity: Option<String>,
+) -> Result<String> {
+    let verifier = SshSigVerifier::try_new(allowed_signers, namespace, identity)?;
+    Ok(verifier.verify_signer(reader, sig)?)
+}
+
+// PROTOCOL.sshsig: the signature covers a hash of the message, so it is streamed
+fn signed_data(reader: &mut dyn Read, namespace: &str, hash_alg: HashAlg) -> Result<Vec<u8>> {
+    let hash = match hash_alg {
+        HashAlg::Sha256 => hash_reader::<Sha256>(reader)?,
+        HashAlg::Sha512 => hash_reader::<Sha512>(reader)?,
+        alg => anyhow::bail!("unsupported SSH signature hash {}", alg),
+    };
+    let mut data = b"SSHSIG".to_vec();
+    for field in [
+        namespace.as_bytes(),
+        b"",
+        hash_alg.as_str().as_bytes(),
+        &hash,
+    ] {
+        data.extend((field.len() as u32).to_be_bytes());
+        data.extend(field);
+    }
+    Ok(data)
+}
+
+fn hash_reader<D: Digest>(reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn fingerprint(key: &KeyData) -> String {
+    PublicKey::new(key.clone(), "")
+        .fingerprint(HashAlg::Sha256)
+        .to_string()
+}
+
+/// `principals [options] keytype base64 [comment]`, see ALLOWED SIGNERS in ssh-keygen(1).
+/// Certificate authorities are skipped, certificates are not supported.
+fn parse_allowed_signers(input: &str) -> Result<Vec<AllowedSigner>, SshSigError> {
+    let mut signers = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |reason: &str| SshSigError::AllowedSigners {
+            line: i + 1,
+            reason: reason.to_string(),
+        };
+
+        let fields = split_unquoted(line, char::is_whitespace);
+        let (principals, options, key) = match &fields[..] {
+            [principals, key_type, key, ..] if is_key_type(key_type) => {
+                (principals, None, format!("{} {}", key_type, key))
+            }
+            [principals, options, key_type, key, ..] => {
+                (principals, Some(options), format!("{} {}", key_type, key))
+            }
+            _ => return Err(error("expected principals, options, key type and key")),
+        };
+
+        let mut signer = AllowedSigner {
+            principals: principals.to_string(),
+            namespaces: None,
+            valid_after: None,
+            valid_before: None,
+            key: PublicKey::from_openssh(&key)
+                .map_err(|e| error(&e.to_string()))?
+                .key_data()
+                .clone(),
+        };
+        let mut cert_authority = false;
+        for option in options
+            .map(|o| split_unquoted(o, |c| c == ','))
+            .unwrap_or_default()
+        {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim_matches('"'))),
+                None => (option.as_str(), None),
+            };
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("cert-authority", None) => cert_authority = true,
+                ("namespaces", Some(value)) => {
+                    signer.namespaces = Some(value.split(',').map(String::from).collect())
+                }
+                ("valid-after", Some(value)) => {
+                    signer.valid_after =
+                        Some(parse_time(value).ok_or_else(|| error("invalid valid-after"))?)
+                }
+                ("valid-before", Some(value)) => {
+                    signer.valid_before =
+                        Some(parse_time(value).ok_or_else(|| error("invalid valid-before"))?)
+                }
+                _ => return Err(error(&format!("unsupported option {}", option))),
+            }
+        }
+        if !cert_authority {
+            signers.push(signer);
+        }
+    }
+    Ok(signers)
+}
+
+fn is_key_type(s: &str) -> bool {
+    s.starts_with("ssh-") || s.starts_with("ecdsa-") || s.starts_with("sk-")
+}
+
+// split on `sep` outside of double quotes, quotes are kept
+fn split_unquoted(s: &str, sep: impl Fn(char) -> bool) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if !quoted && sep(c) {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// `YYYYMMDD[HHMM[SS]]` in local time, or UTC with a trailing `Z`.
+fn parse_time(s: &str) -> Option<i64> {
+    let (s, utc) = match s.strip_suffix(['Z', 'z']) {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    let time = match s.len() {
+        8 => NaiveDate::parse_from_str(s, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+        12 => NaiveDateTime::parse_from_str(s, "%Y%m%d%H%M").ok()?,
+        14 => NaiveDateTime::parse_from_str(s, "%Y%m%d%H%M%S").ok()?,
+        _ => return None,
+    };
+    if utc {
+        Some(time.and_utc().timestamp())
+    } else {
+        Local
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|t| t.timestamp())
+    }
+}
+
+/// OpenSSH pattern lists: `*` and `?` wildcards, a `!` pattern that matches rejects.
+fn match_pattern_list(patterns: &[String], s: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if glob_match(negated.as_bytes(), s.as_bytes()) => return false,
+            Some(_) => {}
+            None => matched |= glob_match(pattern.as_bytes(), s.as_bytes()),
+        }
+    }
+    matched
+}
+
+// two pointer wildcard match, a mismatch only backtracks to the last `*`, so
+// patterns with many stars stay linear
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    let mut star = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((star_p, star_i)) => {
+                    star = Some((star_p, star_i + 1));
+                    p = star_p + 1;
+                    i = star_i + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed_signer(options: &str, key: &SigningKey) -> Result<String> {
+        let public = encode_ed25519_verifying_key(&key.verifying_key(), KeyFormat::OpenSsh)?;
+        Ok(format!(
+            "dev@example.com,ci@* {} {}",
+            options,
+            String::from_utf8(public)?
+        ))
+    }
+
+    #[test]
+    fn test_sshsig_round_trip() -> Result<()> {
+        let key = SigningKey::from_bytes(&[3; 32]);
+        let sig = process_text_sign_sshsig(&mut "hello".as_bytes(), &key.to_bytes(), "git")?;
+        assert!(sig.starts_with("-----BEGIN SSH SIGNATURE-----\n"));
+
+        let allowed = allowed_signer("namespaces=\"git,file\"", &key)?;
+        let verify = |input: &str, namespace: &str, identity: Option<&str>| {
+            let verifier =
+                SshSigVerifier::try_new(allowed.as_bytes(), namespace, identity.map(String::from))?;
+            Ok::<_, anyhow::Error>(verifier.verify_signer(&mut input.as_bytes(), sig.as_bytes()))
+        };
+        assert_eq!(
+            verify("hello", "git", None)?,
+            Ok("dev@example.com,ci@*".into())
+        );
+        assert!(verify("hello", "git", Some("ci@build01"))?.is_ok());
+        assert_eq!(
+            verify("hellO", "git", None)?,
+            Err(SshSigError::InvalidSignature)
+        );
+        assert!(matches!(
+            verify("hello", "git", Some("eve@example.com"))?,
+            Err(SshSigError::NotAllowed(_))
+        ));
+        assert!(matches!(
+            verify("hello", "file", None)?,
+            Err(SshSigError::NamespaceMismatch { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_ssh_keygen_signature() -> Result<()> {
+        // `ssh-keygen -Y sign -f key -n file msg`
+        let allowed = "dev@example.com ssh-ed25519 \
+            AAAAC3NzaC1lZDI1NTE5AAAAIAN214uHq7CKTDdSiwgaG0+CmkbAifRNo8eu9maqyS4M";
+        let sig = "-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgA3bXi4ersIpMN1KLCBobT4KaRs
+CJ9E2jx672ZqrJLgwAAAAEZmlsZQAAAAAAAAAGc2hhNTEyAAAAUwAAAAtzc2gtZWQyNTUx
+OQAAAEDtmw1SnitaM47P0d11XwHEKQfxwmu3f566SdrX/i/lSy9gzNpX3SDz2IDA6E62TL
+Rm/L3L9H4b9C+GA6fMTVcG
+-----END SSH SIGNATURE-----
+";
+        let verifier =
+            SshSigVerifier::try_new(allowed.as_bytes(), "file", Some("dev@example.com".into()))?;
+        assert_eq!(
+            verifier.verify_signer(&mut "hello from ssh-keygen\n".as_bytes(), sig.as_bytes()),
+            Ok("dev@example.com".into())
+        );
+        assert_eq!(
+            verifier.verify_signer(&mut "hello from ssh-keygen".as_bytes(), sig.as_bytes()),
+            Err(SshSigError::InvalidSignature)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"ci@*", b"ci@build01"));
+        assert!(glob_match(b"*@example.?om", b"dev@example.com"));
+        assert!(glob_match(b"a*b*c", b"abbbc"));
+        assert!(!glob_match(b"a*b*c", b"abbcb"));
+        assert!(glob_match(b"**", b""));
+        assert!(!glob_match(b"?", b""));
+        // would take exponential time with naive backtracking
+        let s = "a".repeat(100);
+        assert!(!glob_match(
+            format!("{}b", "*a".repeat(30)).as_bytes(),
+            s.as_bytes()
+        ));
+    }
+
+    #[test]
+    fn test_allowed_signers_options() -> Result<()> {
+        let key = SigningKey::from_bytes(&[3; 32]);
+        let sig = process_text_sign_sshsig(&mut "hello".as_bytes(), &key.to_bytes(), "file")?;
+        for (options, allowed) in [
+            ("namespaces=\"git\"", false),
+            ("valid-before=\"19991231Z\"", false),
+            (
+                "valid-after=\"20000101\",valid-before=\"99991231235959Z\"",
+                true,
+            ),
+            ("cert-authority", false),
+        ] {
+            let signers = allowed_signer(options, &key)?;
+            let verified = process_text_verify_sshsig(
+                &mut "hello".as_bytes(),
+                signers.as_bytes(),
+                sig.as_bytes(),
+                "file",
+                None,
+            );
+            assert_eq!(verified.is_ok(), allowed, "{}", options);
+        }
+        assert!(parse_allowed_signers("dev@example.com bogus=1 ssh-ed25519 AAAA").is_err());
+        Ok(())
+    }
+}
@@ -1,22 +1,55 @@
-use crate::{process_genpwd, TextSignFormat};
+use crate::{
+    encode_blake3_key, encode_ecdsa_keys, encode_ed25519_signing_key, encode_ed25519_verifying_key,
+    encode_hmac_key, load_blake3_key, load_ecdsa_signing_key, load_ecdsa_verifying_key,
+    load_ed25519_signing_key, load_ed25519_verifying_key, load_hmac_key, EcdsaCurve,
+    EcdsaSigningKey, EcdsaVerifyingKey, KeyFormat, SshSigVerifier, SshSigner, TextSignFormat,
+    SSHSIG_DEFAULT_NAMESPACE,
+};
 use anyhow::Result;
-use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
-use std::{collections::HashMap, io::Read};
+use ed25519_dalek::{Digest, Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use p256::ecdsa::signature::{DigestSigner, DigestVerifier};
+use sha2::{Sha256, Sha512};
+use std::{collections::HashMap, io::Read, marker::PhantomData};
 
-use rand::rngs::OsRng;
+use rand::{rngs::OsRng, RngCore};
+
+// inputs are hashed as they are read, so signing a file never holds more than this
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
 
 pub trait TextSigner {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>>;
+    /// Fingerprint of the key that verifies these signatures, see `key_id`.
+    fn key_id(&self) -> String;
 }
 
 pub trait TextVerifier {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool>;
+    fn key_id(&self) -> String;
 }
 
 pub struct Blake3 {
     key: [u8; 32],
 }
 
+/// HMAC with any hash, `M` is e.g. `Hmac<Sha256>`.
+pub struct HmacKey<M> {
+    key: Vec<u8>,
+    mac: PhantomData<fn() -> M>,
+}
+
+pub type HmacSha256 = HmacKey<Hmac<Sha256>>;
+pub type HmacSha512 = HmacKey<Hmac<Sha512>>;
+
+/// ECDSA over the SHA-256 of the input, with RFC 6979 deterministic nonces.
+pub struct EcdsaSigner {
+    key: EcdsaSigningKey,
+}
+
+pub struct EcdsaVerifier {
+    key: EcdsaVerifyingKey,
+}
+
 pub struct Ed25519Signer {
     key: SigningKey,
 }
@@ -25,40 +58,103 @@ pub struct Ed25519Verifier {
     key: VerifyingKey,
 }
 
+/// Ed25519ph (RFC 8032): signs the SHA-512 of the input, so it can be streamed.
+/// Its signatures are not plain Ed25519 ones, the same key gives different results.
+pub struct Ed25519phSigner {
+    key: SigningKey,
+}
+
+pub struct Ed25519phVerifier {
+    key: VerifyingKey,
+}
+
 impl Ed25519Verifier {
+    /// Accepts any format `load_ed25519_verifying_key` understands.
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        let key = VerifyingKey::from_bytes(key)?;
+        let key = load_ed25519_verifying_key(key.as_ref())?;
         Ok(Self { key })
     }
+
+    /// Counterpart of `Ed25519Signer::sign_message`.
+    pub fn verify_message(&self, message: &[u8], sig: &[u8]) -> bool {
+        match <[u8; 64]>::try_from(sig) {
+            Ok(sig) => self
+                .key
+                .verify(message, &Signature::from_bytes(&sig))
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
 }
 
 impl TextSigner for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        //let ret = blake3
-        let ret = blake3::keyed_hash(&self.key, &buf);
-        Ok(ret.as_bytes().to_vec())
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        for_each_chunk(reader, |chunk| {
+            hasher.update(chunk);
+        })?;
+        Ok(hasher.finalize().as_bytes().to_vec())
+    }
+
+    fn key_id(&self) -> String {
+        key_id(&self.key)
     }
 }
 
 impl TextVerifier for Blake3 {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let ret = blake3::keyed_hash(&self.key, &buf);
-        Ok(ret.as_bytes() == sig)
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        for_each_chunk(reader, |chunk| {
+            hasher.update(chunk);
+        })?;
+        let Ok(sig) = <[u8; 32]>::try_from(sig) else {
+            return Ok(false);
+        };
+        // blake3::Hash compares in constant time
+        Ok(hasher.finalize() == blake3::Hash::from(sig))
+    }
+
+    fn key_id(&self) -> String {
+        key_id(&self.key)
+    }
+}
+
+impl<M: Mac + KeyInit> TextSigner for HmacKey<M> {
+    fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let mut mac = self.mac();
+        for_each_chunk(reader, |chunk| mac.update(chunk))?;
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+
+    fn key_id(&self) -> String {
+        key_id(&self.key)
+    }
+}
+
+impl<M: Mac + KeyInit> TextVerifier for HmacKey<M> {
+    fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        let mut mac = self.mac();
+        for_each_chunk(reader, |chunk| mac.update(chunk))?;
+        // constant time, unlike comparing the bytes
+        Ok(mac.verify_slice(sig).is_ok())
+    }
+
+    fn key_id(&self) -> String {
+        key_id(&self.key)
     }
 }
 
+/// PureEdDSA hashes the message twice when signing, so the input is read into memory,
+/// use `Ed25519phSigner` for large inputs.
 impl TextSigner for Ed25519Signer {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
-        let signature = self.key.sign(&buf);
-        Ok(signature.to_bytes().to_vec())
+        Ok(self.sign_message(&buf))
+    }
+
+    fn key_id(&self) -> String {
+        key_id(self.key.verifying_key().as_bytes())
     }
 }
 
@@ -66,17 +162,47 @@ impl TextVerifier for Ed25519Verifier {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
-        let sig = (&sig[..64]).try_into()?;
-        let signature = Signature::from_bytes(sig);
-        Ok(self.key.verify(&buf, &signature).is_ok())
+        Ok(self.verify_message(&buf, sig))
+    }
+
+    fn key_id(&self) -> String {
+        key_id(self.key.as_bytes())
+    }
+}
+
+impl TextSigner for Ed25519phSigner {
+    fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let mut hasher = Sha512::new();
+        for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+        let signature = self.key.sign_prehashed(hasher, None)?;
+        Ok(signature.to_bytes().to_vec())
+    }
+
+    fn key_id(&self) -> String {
+        key_id(self.key.verifying_key().as_bytes())
+    }
+}
+
+impl TextVerifier for Ed25519phVerifier {
+    fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        let mut hasher = Sha512::new();
+        for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+        let Ok(sig) = <[u8; 64]>::try_from(sig) else {
+            return Ok(false);
+        };
+        let signature = Signature::from_bytes(&sig);
+        Ok(self.key.verify_prehashed(hasher, None, &signature).is_ok())
+    }
+
+    fn key_id(&self) -> String {
+        key_id(self.key.as_bytes())
     }
 }
 
 impl Blake3 {
+    /// Accepts what `load_blake3_key` does, legacy key files included.
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        Ok(Self::new(key))
+        Ok(Self::new(load_blake3_key(key.as_ref())?))
     }
 
     pub fn new(key: [u8; 32]) -> Self {
@@ -84,19 +210,121 @@ impl Blake3 {
     }
 
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpwd(32, true, true, true, true)?;
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        let mut map = HashMap::new();
+        map.insert(
+            secret_key_name(TextSignFormat::Blake3),
+            encode_blake3_key(&key).into_bytes(),
+        );
+        Ok(map)
+    }
+}
+
+impl<M: Mac + KeyInit> HmacKey<M> {
+    /// Accepts what `load_hmac_key` does, a generated key or any other file.
+    pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
+        Ok(Self::new(load_hmac_key(key.as_ref())?))
+    }
+
+    /// HMAC takes a key of any length, all of `key` is the secret.
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: key.as_ref().to_vec(),
+            mac: PhantomData,
+        }
+    }
+
+    /// A random key as long as the hash output.
+    fn generate(format: TextSignFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
+        let mut key = vec![0u8; M::output_size()];
+        OsRng.fill_bytes(&mut key);
+        let mut map = HashMap::new();
+        map.insert(secret_key_name(format), encode_hmac_key(&key).into_bytes());
+        Ok(map)
+    }
+
+    fn mac(&self) -> M {
+        <M as KeyInit>::new_from_slice(&self.key).expect("HMAC can take a key of any size")
+    }
+}
+
+impl EcdsaSigner {
+    pub fn try_new(curve: EcdsaCurve, key: impl AsRef<[u8]>) -> Result<Self> {
+        let key = load_ecdsa_signing_key(curve, key.as_ref())?;
+        Ok(Self { key })
+    }
+
+    pub fn generate(
+        curve: EcdsaCurve,
+        key_format: KeyFormat,
+    ) -> Result<HashMap<&'static str, Vec<u8>>> {
+        let key = match curve {
+            EcdsaCurve::P256 => EcdsaSigningKey::P256(p256::ecdsa::SigningKey::random(&mut OsRng)),
+            EcdsaCurve::Secp256k1 => {
+                EcdsaSigningKey::Secp256k1(k256::ecdsa::SigningKey::random(&mut OsRng))
+            }
+        };
+        let (sk, pk) = encode_ecdsa_keys(&key, key_format)?;
         let mut map = HashMap::new();
-        //map.insert("blake3.txt", key.as_bytes().to_vec());
-        map.insert("blake3.txt", key.as_bytes().to_vec());
+        let (sk_name, pk_name) = ecdsa_key_names(curve);
+        map.insert(sk_name, sk);
+        map.insert(pk_name, pk);
         Ok(map)
     }
 }
 
+impl EcdsaVerifier {
+    pub fn try_new(curve: EcdsaCurve, key: impl AsRef<[u8]>) -> Result<Self> {
+        let key = load_ecdsa_verifying_key(curve, key.as_ref())?;
+        Ok(Self { key })
+    }
+}
+
+impl TextSigner for EcdsaSigner {
+    fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let mut hasher = Sha256::new();
+        for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+        Ok(match &self.key {
+            EcdsaSigningKey::P256(key) => {
+                let sig: p256::ecdsa::Signature = key.try_sign_digest(hasher)?;
+                sig.to_bytes().to_vec()
+            }
+            EcdsaSigningKey::Secp256k1(key) => {
+                let sig: k256::ecdsa::Signature = key.try_sign_digest(hasher)?;
+                sig.to_bytes().to_vec()
+            }
+        })
+    }
+
+    fn key_id(&self) -> String {
+        key_id(&self.key.verifying_key().to_compressed())
+    }
+}
+
+/// Signatures are `r || s`, 64 bytes, as in JWS.
+impl TextVerifier for EcdsaVerifier {
+    fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        let mut hasher = Sha256::new();
+        for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+        Ok(match &self.key {
+            EcdsaVerifyingKey::P256(key) => p256::ecdsa::Signature::from_slice(sig)
+                .is_ok_and(|sig| key.verify_digest(hasher, &sig).is_ok()),
+            EcdsaVerifyingKey::Secp256k1(key) => k256::ecdsa::Signature::from_slice(sig)
+                .is_ok_and(|sig| key.verify_digest(hasher, &sig).is_ok()),
+        })
+    }
+
+    fn key_id(&self) -> String {
+        key_id(&self.key.to_compressed())
+    }
+}
+
 impl Ed25519Signer {
+    /// Accepts any format `load_ed25519_signing_key` understands.
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        Ok(Self::new(key))
+        let key = load_ed25519_signing_key(key.as_ref())?;
+        Ok(Self { key })
     }
 
     pub fn new(key: &[u8; 32]) -> Self {
@@ -104,28 +332,82 @@ impl Ed25519Signer {
         Self { key }
     }
 
-    pub fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
+    /// PureEdDSA over a message in memory, for formats such as JWS that require it.
+    pub fn sign_message(&self, message: &[u8]) -> Vec<u8> {
+        self.key.sign(message).to_bytes().to_vec()
+    }
+
+    pub fn generate(key_format: KeyFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
         let mut csprng = OsRng;
         let sk: SigningKey = SigningKey::generate(&mut csprng);
         let pk: VerifyingKey = (&sk).into();
         let mut map = HashMap::new();
-        map.insert("ed25519.sk", sk.to_bytes().to_vec());
-        map.insert("ed25519.pk", pk.to_bytes().to_vec());
+        map.insert(
+            secret_key_name(TextSignFormat::Ed25519),
+            encode_ed25519_signing_key(&sk, key_format)?,
+        );
+        map.insert("ed25519.pk", encode_ed25519_verifying_key(&pk, key_format)?);
 
         Ok(map)
     }
 }
 
+impl Ed25519phSigner {
+    /// Same keys as `Ed25519Signer`.
+    pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
+        let key = load_ed25519_signing_key(key.as_ref())?;
+        Ok(Self { key })
+    }
+}
+
+impl Ed25519phVerifier {
+    pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
+        let key = load_ed25519_verifying_key(key.as_ref())?;
+        Ok(Self { key })
+    }
+}
+
+pub fn text_signer(format: TextSignFormat, key: &[u8]) -> Result<Box<dyn TextSigner>> {
+    Ok(match format {
+        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
+        TextSignFormat::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
+        TextSignFormat::Ed25519ph => Box::new(Ed25519phSigner::try_new(key)?),
+        TextSignFormat::HmacSha256 => Box::new(HmacSha256::try_new(key)?),
+        TextSignFormat::HmacSha512 => Box::new(HmacSha512::try_new(key)?),
+        TextSignFormat::EcdsaP256 => Box::new(EcdsaSigner::try_new(EcdsaCurve::P256, key)?),
+        TextSignFormat::EcdsaSecp256k1 => {
+            Box::new(EcdsaSigner::try_new(EcdsaCurve::Secp256k1, key)?)
+        }
+        TextSignFormat::SshSig => Box::new(SshSigner::try_new(key, SSHSIG_DEFAULT_NAMESPACE)?),
+    })
+}
+
+pub fn text_verifier(format: TextSignFormat, key: &[u8]) -> Result<Box<dyn TextVerifier>> {
+    Ok(match format {
+        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
+        TextSignFormat::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
+        TextSignFormat::Ed25519ph => Box::new(Ed25519phVerifier::try_new(key)?),
+        TextSignFormat::HmacSha256 => Box::new(HmacSha256::try_new(key)?),
+        TextSignFormat::HmacSha512 => Box::new(HmacSha512::try_new(key)?),
+        TextSignFormat::EcdsaP256 => Box::new(EcdsaVerifier::try_new(EcdsaCurve::P256, key)?),
+        TextSignFormat::EcdsaSecp256k1 => {
+            Box::new(EcdsaVerifier::try_new(EcdsaCurve::Secp256k1, key)?)
+        }
+        // the key is an allowed_signers file
+        TextSignFormat::SshSig => Box::new(SshSigVerifier::try_new(
+            key,
+            SSHSIG_DEFAULT_NAMESPACE,
+            None,
+        )?),
+    })
+}
+
 pub fn process_text_sign(
     reader: &mut dyn Read,
     key: &[u8],
     format: TextSignFormat,
 ) -> Result<Vec<u8>> {
-    let signer: Box<dyn TextSigner> = match format {
-        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
-        TextSignFormat::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
-    };
-    signer.sign(reader)
+    text_signer(format, key)?.sign(reader)
 }
 
 pub fn process_text_verify(
@@ -134,39 +416,284 @@ pub fn process_text_verify(
     sig: &[u8],
     format: TextSignFormat,
 ) -> Result<bool> {
-    let verifier: Box<dyn TextVerifier> = match format {
-        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
-        TextSignFormat::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
-    };
+    text_verifier(format, key)?.verify(reader, sig)
+}
+
+/// Short fingerprint of a public key, or of a symmetric key through a one way hash,
+/// so a signature can name the key that made it.
+pub fn key_id(key: &[u8]) -> String {
+    let hash = blake3::derive_key("zcli key id v1", key);
+    hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
 
-    verifier.verify(reader, sig)
+/// File name of the secret part in the map returned by `process_text_key_generate`.
+pub fn secret_key_name(format: TextSignFormat) -> &'static str {
+    match format {
+        TextSignFormat::Blake3 => "blake3.txt",
+        TextSignFormat::Ed25519 | TextSignFormat::Ed25519ph => "ed25519.sk",
+        TextSignFormat::HmacSha256 => "hmac-sha256.key",
+        TextSignFormat::HmacSha512 => "hmac-sha512.key",
+        TextSignFormat::EcdsaP256 => "ecdsa-p256.sk",
+        TextSignFormat::EcdsaSecp256k1 => "ecdsa-secp256k1.sk",
+        TextSignFormat::SshSig => "id_ed25519",
+    }
 }
 
-pub fn process_text_key_generate(format: TextSignFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
+fn ecdsa_key_names(curve: EcdsaCurve) -> (&'static str, &'static str) {
+    match curve {
+        EcdsaCurve::P256 => (secret_key_name(TextSignFormat::EcdsaP256), "ecdsa-p256.pk"),
+        EcdsaCurve::Secp256k1 => (
+            secret_key_name(TextSignFormat::EcdsaSecp256k1),
+            "ecdsa-secp256k1.pk",
+        ),
+    }
+}
+
+pub fn process_text_key_generate(
+    format: TextSignFormat,
+    key_format: KeyFormat,
+) -> Result<HashMap<&'static str, Vec<u8>>> {
     match format {
-        TextSignFormat::Blake3 => Blake3::generate(),
-        TextSignFormat::Ed25519 => Ed25519Signer::generate(),
+        TextSignFormat::Blake3 => {
+            anyhow::ensure!(
+                key_format == KeyFormat::Raw,
+                "blake3 keys are only written raw"
+            );
+            Blake3::generate()
+        }
+        TextSignFormat::Ed25519 | TextSignFormat::Ed25519ph => Ed25519Signer::generate(key_format),
+        TextSignFormat::HmacSha256 | TextSignFormat::HmacSha512 => {
+            anyhow::ensure!(
+                key_format == KeyFormat::Raw,
+                "hmac keys are only written raw"
+            );
+            match format {
+                TextSignFormat::HmacSha256 => HmacSha256::generate(format),
+                _ => HmacSha512::generate(format),
+            }
+        }
+        TextSignFormat::EcdsaP256 => EcdsaSigner::generate(EcdsaCurve::P256, key_format),
+        TextSignFormat::EcdsaSecp256k1 => EcdsaSigner::generate(EcdsaCurve::Secp256k1, key_format),
+        TextSignFormat::SshSig => {
+            anyhow::ensure!(
+                matches!(key_format, KeyFormat::Raw | KeyFormat::OpenSsh),
+                "sshsig keys are written in the OpenSSH format"
+            );
+            SshSigner::generate()
+        }
+    }
+}
+
+pub(crate) fn for_each_chunk(reader: &mut dyn Read, mut f: impl FnMut(&[u8])) -> Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        f(&buf[..n]);
     }
 }
-/*
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    //use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 
-    const KEY: &[u8] = include_bytes!("../../fixtures/blake3.txt");
+    const KEY: &[u8] = b"z8A#kWq2+pL0vN4e!Yt7cR5uJ1gS9hBm";
+
+    /// Generates `len` bytes and fails if it is ever asked for more than a chunk at
+    /// once, as `read_to_end` would.
+    struct BoundedReader {
+        remaining: usize,
+    }
+
+    impl Read for BoundedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            assert!(
+                buf.len() <= CHUNK_SIZE,
+                "read into a {} byte buffer",
+                buf.len()
+            );
+            let n = buf.len().min(self.remaining);
+            buf[..n].fill(self.remaining as u8);
+            self.remaining -= n;
+            Ok(n)
+        }
+    }
 
     #[test]
     fn test_process_text_sign() -> Result<()> {
-        let mut reader = "hello".as_bytes();
-        let mut reader2 = "hello".as_bytes();
-        let format = TextSignFormat::Blake3;
-        let sig = process_text_sign(&mut reader, KEY, format)?;
-        let ret = process_text_verify(&mut reader2, KEY, &sig, format)?;
+        let ed25519 = process_text_key_generate(TextSignFormat::Ed25519, KeyFormat::Raw)?;
+        let keys = [
+            (TextSignFormat::Blake3, KEY, KEY),
+            (
+                TextSignFormat::Ed25519,
+                &ed25519["ed25519.sk"][..],
+                &ed25519["ed25519.pk"][..],
+            ),
+        ];
+        for (format, sk, pk) in keys {
+            let mut reader = "hello".as_bytes();
+            let mut reader2 = "hello".as_bytes();
+            let sig = process_text_sign(&mut reader, sk, format)?;
+            assert!(process_text_verify(&mut reader2, pk, &sig, format)?);
+            assert!(!process_text_verify(
+                &mut "hellO".as_bytes(),
+                pk,
+                &sig,
+                format
+            )?);
+            assert!(!process_text_verify(
+                &mut "hello".as_bytes(),
+                pk,
+                &sig[1..],
+                format
+            )?);
+        }
+        Ok(())
+    }
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_known_vectors() -> Result<()> {
+        // RFC 8032 7.1 test 2, plain Ed25519 as `text sign` made it before ed25519ph,
+        // and 7.3, Ed25519ph
+        let sk = unhex("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb");
+        let pk = unhex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c");
+        let sig = unhex(
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        );
+        assert_eq!(Ed25519Signer::try_new(&sk)?.sign(&mut &[0x72][..])?, sig);
+        assert!(process_text_verify(
+            &mut &[0x72][..],
+            &pk,
+            &sig,
+            TextSignFormat::Ed25519
+        )?);
+        assert!(!process_text_verify(
+            &mut &[0x72][..],
+            &pk,
+            &sig,
+            TextSignFormat::Ed25519ph
+        )?);
+
+        let sk = unhex("833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42");
+        let pk = unhex("ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf");
+        let sig = unhex(
+            "98a70222f0b8121aa9d30f813d683f809e462b469c7ff87639499bb94e6dae41\
+             31f85042463c2a355a2003d062adf5aaa10b8c61e636062aaad11c2a26083406",
+        );
+        assert_eq!(
+            Ed25519phSigner::try_new(&sk)?.sign(&mut "abc".as_bytes())?,
+            sig
+        );
+        assert!(process_text_verify(
+            &mut "abc".as_bytes(),
+            &pk,
+            &sig,
+            TextSignFormat::Ed25519ph
+        )?);
+
+        // RFC 4231 test case 2
+        let mac = HmacSha256::new("Jefe").sign(&mut "what do ya want for nothing?".as_bytes())?;
+        assert_eq!(
+            mac,
+            unhex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+        let mac = HmacSha512::new("Jefe").sign(&mut "what do ya want for nothing?".as_bytes())?;
+        assert_eq!(
+            mac,
+            unhex(
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+                 9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+            )
+        );
 
-        assert!(ret);
+        // RFC 6979 A.2.5, P-256 with SHA-256
+        let sk = unhex("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721");
+        let signer = EcdsaSigner::try_new(EcdsaCurve::P256, &sk)?;
+        let pk = match signer.key.verifying_key() {
+            EcdsaVerifyingKey::P256(pk) => pk.to_encoded_point(false).as_bytes().to_vec(),
+            _ => unreachable!(),
+        };
+        let verifier = EcdsaVerifier::try_new(EcdsaCurve::P256, &pk)?;
+        for (msg, sig) in [
+            (
+                "sample",
+                "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716\
+                 f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+            ),
+            (
+                "test",
+                "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367\
+                 019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+            ),
+        ] {
+            assert_eq!(signer.sign(&mut msg.as_bytes())?, unhex(sig), "{}", msg);
+            assert!(verifier.verify(&mut msg.as_bytes(), &unhex(sig))?);
+        }
+        assert_eq!(
+            pk[1..33],
+            unhex("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6")
+        );
 
+        // RFC 6979 nonces on secp256k1, the usual private key 1 vector, low s
+        let mut sk = [0u8; 32];
+        sk[31] = 1;
+        let signer = EcdsaSigner::try_new(EcdsaCurve::Secp256k1, sk)?;
+        assert_eq!(
+            signer.sign(&mut "Satoshi Nakamoto".as_bytes())?,
+            unhex(
+                "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8\
+                 2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_streams_large_input() -> Result<()> {
+        const LEN: usize = 8 * 1024 * 1024 + 7;
+        let ed25519 = process_text_key_generate(TextSignFormat::Ed25519ph, KeyFormat::Raw)?;
+        let p256 = process_text_key_generate(TextSignFormat::EcdsaP256, KeyFormat::Pem)?;
+        let signers: [(Box<dyn TextSigner>, Box<dyn TextVerifier>); 4] = [
+            (
+                Box::new(Blake3::try_new(KEY)?),
+                Box::new(Blake3::try_new(KEY)?),
+            ),
+            (
+                Box::new(HmacSha256::new(KEY)),
+                Box::new(HmacSha256::new(KEY)),
+            ),
+            (
+                Box::new(Ed25519phSigner::try_new(&ed25519["ed25519.sk"])?),
+                Box::new(Ed25519phVerifier::try_new(&ed25519["ed25519.pk"])?),
+            ),
+            (
+                Box::new(EcdsaSigner::try_new(
+                    EcdsaCurve::P256,
+                    &p256["ecdsa-p256.sk"],
+                )?),
+                Box::new(EcdsaVerifier::try_new(
+                    EcdsaCurve::P256,
+                    &p256["ecdsa-p256.pk"],
+                )?),
+            ),
+        ];
+        for (signer, verifier) in signers {
+            let sig = signer.sign(&mut BoundedReader { remaining: LEN })?;
+            assert!(verifier.verify(&mut BoundedReader { remaining: LEN }, &sig)?);
+            assert!(!verifier.verify(&mut BoundedReader { remaining: LEN - 1 }, &sig)?);
+        }
         Ok(())
     }
 }
-*/
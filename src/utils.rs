@@ -1,5 +1,8 @@
 use anyhow::Result;
-use std::{fs::File, io::Read};
+use std::{
+    fs::File,
+    io::{self, BufRead, IsTerminal, Read, Write},
+};
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -11,9 +14,58 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     Ok(reader)
 }
 
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(output)?)
+    };
+
+    Ok(writer)
+}
+
 pub fn get_content(input: &str) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
     Ok(buf)
 }
+
+/// Read a secret without echoing it: prompt on the terminal if stdin is one,
+/// otherwise take the first line of piped stdin.
+pub fn read_secret(prompt: &str) -> Result<String> {
+    if io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password(prompt)?);
+    }
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// A rough human readable duration, like `3 hours` or `centuries`.
+pub(crate) fn display_time(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = MINUTE * 60.0;
+    const DAY: f64 = HOUR * 24.0;
+    const MONTH: f64 = DAY * 31.0;
+    const YEAR: f64 = MONTH * 12.0;
+    const CENTURY: f64 = YEAR * 100.0;
+
+    let (n, unit) = match seconds {
+        s if s < 1.0 => return "less than a second".to_string(),
+        s if s < MINUTE => (s, "second"),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < MONTH => (s / DAY, "day"),
+        s if s < YEAR => (s / MONTH, "month"),
+        s if s < CENTURY => (s / YEAR, "year"),
+        _ => return "centuries".to_string(),
+    };
+    let n = n.round() as u64;
+    if n == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", n, unit)
+    }
+}